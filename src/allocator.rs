@@ -1,3 +1,4 @@
+use core::fmt;
 use linked_list_allocator::LockedHeap;
 use x86_64::{
     structures::paging::{
@@ -7,35 +8,95 @@ use x86_64::{
 };
 
 pub const HEAP_START: usize = 0x_4444_4444_0000;
+// HEAP_SIZE is the smallest heap the kernel runs with, see init_heap
 pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
 
 #[global_allocator]
 static ALLOCATOR: LockedHeap = LockedHeap::empty();
 
+// HeapInitError describes why init_heap failed
+#[derive(Debug)]
+pub enum HeapInitError {
+    // no frame was left to back a heap page
+    FrameAllocationFailed,
+    // a heap page could not be mapped
+    MapToFailed(MapToError<Size4KiB>),
+}
+
+impl fmt::Display for HeapInitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeapInitError::FrameAllocationFailed => write!(f, "out of frames for the heap"),
+            HeapInitError::MapToFailed(err) => write!(f, "mapping a heap page failed: {:?}", err),
+        }
+    }
+}
+
+impl From<MapToError<Size4KiB>> for HeapInitError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        match err {
+            MapToError::FrameAllocationFailed => HeapInitError::FrameAllocationFailed,
+            err => HeapInitError::MapToFailed(err),
+        }
+    }
+}
+
+// init_heap maps a heap of size bytes, but at least HEAP_SIZE. If the frames
+// run out past HEAP_SIZE it keeps the smaller heap. It returns the size of the
+// heap.
 pub fn init_heap(
     mapper: &mut impl Mapper<Size4KiB>,
     frame_allocator: &mut impl FrameAllocator<Size4KiB>,
-) -> Result<(), MapToError<Size4KiB>> {
+    size: usize,
+) -> Result<usize, HeapInitError> {
+    let size = size.max(HEAP_SIZE);
     let page_range = {
         let heap_start = VirtAddr::new(HEAP_START as u64);
-        let heap_end = heap_start + HEAP_SIZE - 1u64;
+        let heap_end = heap_start + size - 1u64;
         let heap_start_page = Page::containing_address(heap_start);
         let heap_end_page = Page::containing_address(heap_end);
         Page::range_inclusive(heap_start_page, heap_end_page)
     };
 
+    let mut mapped = 0;
     for page in page_range {
-        let frame = frame_allocator
-            .allocate_frame()
-            .ok_or(MapToError::FrameAllocationFailed)?;
+        let frame = match frame_allocator.allocate_frame() {
+            Some(frame) => frame,
+            None if mapped >= HEAP_SIZE => break,
+            None => return Err(HeapInitError::FrameAllocationFailed),
+        };
         let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
         unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
+        mapped += page.size() as usize;
     }
 
     unsafe {
-        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+        ALLOCATOR.lock().init(HEAP_START, mapped);
     }
 
-    Ok(())
+    Ok(mapped)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heap_init_error_display() {
+        assert_eq!(
+            alloc::format!("{}", HeapInitError::FrameAllocationFailed),
+            "out of frames for the heap"
+        );
+        assert_eq!(
+            alloc::format!(
+                "{}",
+                HeapInitError::from(MapToError::<Size4KiB>::ParentEntryHugePage)
+            ),
+            "mapping a heap page failed: ParentEntryHugePage"
+        );
+        assert!(matches!(
+            HeapInitError::from(MapToError::<Size4KiB>::FrameAllocationFailed),
+            HeapInitError::FrameAllocationFailed
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Index;
+
+// RingBuffer is a queue of at most N items. The storage is allocated on the
+// heap once by new, so it never reallocates and doesn't take up N items on
+// the stack. Items are pushed at the back and popped from the front.
+pub struct RingBuffer<T: Copy + Default, const N: usize> {
+    data: Box<[T]>,
+    // the index of the front item in data
+    head: usize,
+    len: usize,
+}
+
+impl<T: Copy + Default, const N: usize> RingBuffer<T, N> {
+    pub fn new() -> Self {
+        RingBuffer {
+            data: vec![T::default(); N].into_boxed_slice(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    // push_back adds item behind the last one and panics if the buffer is
+    // full
+    pub fn push_back(&mut self, item: T) {
+        assert!(self.len < N, "RingBuffer is full, it holds {} items", N);
+        self.data[(self.head + self.len) % N] = item;
+        self.len += 1;
+    }
+
+    // pop_front removes and returns the first item
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let item = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(item)
+    }
+
+    // back returns the last item pushed
+    #[allow(dead_code)]
+    pub fn back(&self) -> Option<&T> {
+        self.len.checked_sub(1).map(|i| &self[i])
+    }
+
+    // iter returns the items from front to back
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).map(move |i| &self[i])
+    }
+
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|i| i == item)
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// indexing counts from the front item
+impl<T: Copy + Default, const N: usize> Index<usize> for RingBuffer<T, N> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        assert!(
+            i < self.len,
+            "index {} out of range for length {}",
+            i,
+            self.len
+        );
+        &self.data[(self.head + i) % N]
+    }
+}
+
+impl<T: Copy + Default, const N: usize> FromIterator<T> for RingBuffer<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut buffer = Self::new();
+        for item in items {
+            buffer.push_back(item);
+        }
+        buffer
+    }
+}
+
+// two buffers are equal if they hold the same items, wherever they are stored
+impl<T: Copy + Default + PartialEq, const N: usize> PartialEq for RingBuffer<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Copy + Default + Eq, const N: usize> Eq for RingBuffer<T, N> {}
+
+impl<T: Copy + Default + fmt::Debug, const N: usize> fmt::Debug for RingBuffer<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_push_pop_wraps_around() {
+        let mut buffer = RingBuffer::<usize, 3>::new();
+        assert!(buffer.is_empty());
+        for i in 0..10 {
+            buffer.push_back(i);
+            if buffer.len() == 3 {
+                assert_eq!(buffer.pop_front(), Some(i - 2));
+            }
+        }
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [8, 9]);
+        assert_eq!((buffer[0], buffer[1]), (8, 9));
+        assert_eq!(buffer.back(), Some(&9));
+        assert!(buffer.contains(&9));
+        assert!(!buffer.contains(&7));
+
+        // equality ignores where the items are stored
+        let other: RingBuffer<usize, 3> = [8, 9].iter().copied().collect();
+        assert_eq!(buffer, other);
+        buffer.clear();
+        assert_eq!(buffer.pop_front(), None);
+        assert_eq!(buffer.back(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "RingBuffer is full")]
+    fn test_push_when_full() {
+        let mut buffer = RingBuffer::<usize, 2>::new();
+        for i in 0..3 {
+            buffer.push_back(i);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use core::fmt;
+
+use super::Color;
+
+// HEADER_LEN is the size of the file header and the BITMAPINFOHEADER
+const HEADER_LEN: usize = 54;
+
+/// Reasons why a BMP file can't be decoded.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BmpError {
+    /// The file is shorter than its headers say.
+    Truncated,
+    /// The file doesn't start with "BM".
+    BadSignature,
+    /// Only uncompressed 24-bit bottom-up bitmaps are supported.
+    Unsupported {
+        bits_per_pixel: u16,
+        compression: u32,
+    },
+}
+
+impl fmt::Display for BmpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BmpError::Truncated => write!(f, "BMP file is truncated"),
+            BmpError::BadSignature => write!(f, "not a BMP file"),
+            BmpError::Unsupported {
+                bits_per_pixel,
+                compression,
+            } => write!(
+                f,
+                "unsupported BMP: {} bits per pixel, compression {}",
+                bits_per_pixel, compression
+            ),
+        }
+    }
+}
+
+// BmpImage is a decoded 24-bit BMP. data holds the pixel rows as stored in
+// the file: bottom-up, BGR, each row padded to a multiple of 4 bytes.
+#[derive(Debug)]
+pub struct BmpImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: &'static [u8],
+}
+
+impl BmpImage {
+    // pixel returns the color at (x, y), counted from the top left corner
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let offset = (self.height - 1 - y) * row_len(self.width) + x * 3;
+        let bgr = &self.data[offset..offset + 3];
+        Color::RGB((bgr[2], bgr[1], bgr[0]))
+    }
+}
+
+// decode parses the headers of a 24-bit uncompressed BMP, e.g. one embedded
+// with include_bytes!
+pub fn decode(bytes: &'static [u8]) -> Result<BmpImage, BmpError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(BmpError::Truncated);
+    }
+    if &bytes[0..2] != b"BM" {
+        return Err(BmpError::BadSignature);
+    }
+    let data_offset = read_u32(bytes, 10) as usize;
+    let width = read_u32(bytes, 18) as i32;
+    let height = read_u32(bytes, 22) as i32;
+    let bits_per_pixel = u16::from_le_bytes([bytes[28], bytes[29]]);
+    let compression = read_u32(bytes, 30);
+    // a negative height marks a top-down bitmap
+    if bits_per_pixel != 24 || compression != 0 || width < 0 || height < 0 {
+        return Err(BmpError::Unsupported {
+            bits_per_pixel,
+            compression,
+        });
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let data_len = row_len(width) * height;
+    match bytes.get(data_offset..data_offset + data_len) {
+        Some(data) => Ok(BmpImage {
+            width,
+            height,
+            data,
+        }),
+        None => Err(BmpError::Truncated),
+    }
+}
+
+// row_len returns the size of a pixel row in bytes, including the padding
+fn row_len(width: usize) -> usize {
+    (width * 3 + 3) / 4 * 4
+}
+
+// read_u32 reads the little endian u32 at offset
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+
+    // encode builds a 24-bit BMP from rows of RGB pixels, top row first
+    pub(crate) fn encode(rows: &[&[(u8, u8, u8)]]) -> &'static [u8] {
+        let (width, height) = (rows[0].len(), rows.len());
+        let data_len = row_len(width) * height;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&((HEADER_LEN + data_len) as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&(width as u32).to_le_bytes());
+        bytes.extend_from_slice(&(height as u32).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&24u16.to_le_bytes());
+        bytes.resize(HEADER_LEN, 0);
+        for row in rows.iter().rev() {
+            for (r, g, b) in row.iter() {
+                bytes.extend_from_slice(&[*b, *g, *r]);
+            }
+            bytes.resize(bytes.len() + row_len(width) - width * 3, 0);
+        }
+        Box::leak(bytes.into_boxed_slice())
+    }
+
+    #[test]
+    fn test_decode() {
+        let red = (255, 0, 0);
+        let blue = (0, 0, 255);
+        let bytes = encode(&[&[red, blue, red], &[blue, blue, red]]);
+        let img = decode(bytes).unwrap();
+        assert_eq!((img.width, img.height), (3, 2));
+        // 3 pixels are 9 bytes, padded to 12
+        assert_eq!(img.data.len(), 2 * 12);
+        assert_eq!(img.pixel(0, 0), Color::RGB(red));
+        assert_eq!(img.pixel(1, 0), Color::RGB(blue));
+        assert_eq!(img.pixel(0, 1), Color::RGB(blue));
+        assert_eq!(img.pixel(2, 1), Color::RGB(red));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = encode(&[&[(1, 2, 3)]]);
+        assert_eq!(
+            decode(&bytes[..HEADER_LEN - 1]).unwrap_err(),
+            BmpError::Truncated
+        );
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1]).unwrap_err(),
+            BmpError::Truncated
+        );
+
+        let mut bad = bytes.to_vec();
+        bad[0] = b'X';
+        let bad: &'static [u8] = Box::leak(bad.into_boxed_slice());
+        assert_eq!(decode(bad).unwrap_err(), BmpError::BadSignature);
+
+        let mut bad = bytes.to_vec();
+        bad[28] = 32;
+        let bad: &'static [u8] = Box::leak(bad.into_boxed_slice());
+        assert_eq!(
+            decode(bad).unwrap_err(),
+            BmpError::Unsupported {
+                bits_per_pixel: 32,
+                compression: 0
+            }
+        );
+    }
+}
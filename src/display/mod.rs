@@ -0,0 +1,2546 @@
+use bootloader::boot_info::{FrameBufferInfo, PixelFormat};
+use core::{
+    fmt::{self, Write},
+    ptr,
+};
+use font8x8::UnicodeFonts;
+
+use crate::task::tick::TICKS_PER_SECOND;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[allow(dead_code)]
+pub mod bmp;
+
+// Additional vertical space between lines
+const LINE_SPACING: usize = 2;
+
+// BLOCK_SIZE is the number of pixels in a block
+pub const BLOCK_SIZE: usize = 8;
+
+// LOGO_SCALE is the size in pixels of a single bit of the boot logo
+pub const LOGO_SCALE: usize = 4;
+
+// BOOT_LOGO is a 64x16 bitmap spelling "SNAKEOS". Like in the font8x8 glyphs
+// the lowest bit of each byte is the leftmost pixel.
+pub const BOOT_LOGO: [[u8; 8]; 16] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x7c, 0x04, 0xe1, 0x10, 0xe2, 0x8f, 0x0f, 0x1f],
+    [0x7c, 0x04, 0xe1, 0x10, 0xe2, 0x8f, 0x0f, 0x1f],
+    [0x82, 0x0c, 0x11, 0x11, 0x21, 0x40, 0x90, 0x20],
+    [0x82, 0x0c, 0x11, 0x11, 0x21, 0x40, 0x90, 0x20],
+    [0x02, 0x14, 0x09, 0x92, 0x20, 0x40, 0x90, 0x00],
+    [0x02, 0x14, 0x09, 0x92, 0x20, 0x40, 0x90, 0x00],
+    [0x7c, 0x24, 0xf9, 0x73, 0xe0, 0x43, 0x10, 0x1f],
+    [0x7c, 0x24, 0xf9, 0x73, 0xe0, 0x43, 0x10, 0x1f],
+    [0x80, 0x44, 0x09, 0x92, 0x20, 0x40, 0x10, 0x20],
+    [0x80, 0x44, 0x09, 0x92, 0x20, 0x40, 0x10, 0x20],
+    [0x82, 0x84, 0x09, 0x12, 0x21, 0x40, 0x90, 0x20],
+    [0x82, 0x84, 0x09, 0x12, 0x21, 0x40, 0x90, 0x20],
+    [0x7c, 0x04, 0x09, 0x12, 0xe2, 0x8f, 0x0f, 0x1f],
+    [0x7c, 0x04, 0x09, 0x12, 0xe2, 0x8f, 0x0f, 0x1f],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+// LARGE_DIGITS are the digits 0 to 9 of the font8x8 font with every row
+// doubled, giving 8x16 glyphs
+pub const LARGE_DIGITS: [[u8; 16]; 10] = [
+    // 0
+    [
+        0x3E, 0x3E, 0x63, 0x63, 0x73, 0x73, 0x7B, 0x7B, 0x6F, 0x6F, 0x67, 0x67, 0x3E, 0x3E, 0x00,
+        0x00,
+    ],
+    // 1
+    [
+        0x0C, 0x0C, 0x0E, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x3F, 0x00,
+        0x00,
+    ],
+    // 2
+    [
+        0x1E, 0x1E, 0x33, 0x33, 0x30, 0x30, 0x1C, 0x1C, 0x06, 0x06, 0x33, 0x33, 0x3F, 0x3F, 0x00,
+        0x00,
+    ],
+    // 3
+    [
+        0x1E, 0x1E, 0x33, 0x33, 0x30, 0x30, 0x1C, 0x1C, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x1E, 0x00,
+        0x00,
+    ],
+    // 4
+    [
+        0x38, 0x38, 0x3C, 0x3C, 0x36, 0x36, 0x33, 0x33, 0x7F, 0x7F, 0x30, 0x30, 0x78, 0x78, 0x00,
+        0x00,
+    ],
+    // 5
+    [
+        0x3F, 0x3F, 0x03, 0x03, 0x1F, 0x1F, 0x30, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x1E, 0x00,
+        0x00,
+    ],
+    // 6
+    [
+        0x1C, 0x1C, 0x06, 0x06, 0x03, 0x03, 0x1F, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x1E, 0x00,
+        0x00,
+    ],
+    // 7
+    [
+        0x3F, 0x3F, 0x33, 0x33, 0x30, 0x30, 0x18, 0x18, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x00,
+        0x00,
+    ],
+    // 8
+    [
+        0x1E, 0x1E, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x1E, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x1E, 0x00,
+        0x00,
+    ],
+    // 9
+    [
+        0x1E, 0x1E, 0x33, 0x33, 0x33, 0x33, 0x3E, 0x3E, 0x30, 0x30, 0x18, 0x18, 0x0E, 0x0E, 0x00,
+        0x00,
+    ],
+];
+
+// EXTRA_GLYPHS are narrow glyphs for characters missing in the basic font,
+// they are 3 pixels wide and advance by EXTRA_GLYPH_WIDTH
+const EXTRA_GLYPHS: [(char, [u8; 8]); 4] = [
+    ('↑', [0x02, 0x07, 0x02, 0x02, 0x02, 0x02, 0x02, 0x00]),
+    ('↓', [0x02, 0x02, 0x02, 0x02, 0x02, 0x07, 0x02, 0x00]),
+    ('·', [0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00]),
+    ('•', [0x00, 0x00, 0x02, 0x07, 0x02, 0x00, 0x00, 0x00]),
+];
+const EXTRA_GLYPH_WIDTH: usize = 4;
+
+// TINY_DIGITS are 3x5 digits 0 to 9 on the baseline of the basic font, used
+// instead of its digits while the tiny font is set. They advance by
+// TINY_GLYPH_WIDTH.
+const TINY_DIGITS: [[u8; 8]; 10] = [
+    [0x00, 0x00, 0x07, 0x05, 0x05, 0x05, 0x07, 0x00],
+    [0x00, 0x00, 0x02, 0x03, 0x02, 0x02, 0x07, 0x00],
+    [0x00, 0x00, 0x07, 0x04, 0x07, 0x01, 0x07, 0x00],
+    [0x00, 0x00, 0x07, 0x04, 0x07, 0x04, 0x07, 0x00],
+    [0x00, 0x00, 0x05, 0x05, 0x07, 0x04, 0x04, 0x00],
+    [0x00, 0x00, 0x07, 0x01, 0x07, 0x04, 0x07, 0x00],
+    [0x00, 0x00, 0x07, 0x01, 0x07, 0x05, 0x07, 0x00],
+    [0x00, 0x00, 0x07, 0x04, 0x04, 0x04, 0x04, 0x00],
+    [0x00, 0x00, 0x07, 0x05, 0x07, 0x05, 0x07, 0x00],
+    [0x00, 0x00, 0x07, 0x05, 0x07, 0x04, 0x07, 0x00],
+];
+const TINY_GLYPH_WIDTH: usize = 5;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Color {
+    Black,
+    White,
+    Grey,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    LightGreen,
+    LightRed,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    LightYellow,
+    DarkGreen,
+    DarkRed,
+    DarkBlue,
+    DarkMagenta,
+    DarkCyan,
+    DarkYellow,
+    RGB((u8, u8, u8)),
+    RGB32(u32),
+    // 0xAARRGGBB, blended over the existing pixel by write_pixel
+    RGBA32(u32),
+}
+
+impl Color {
+    // rgb returns the red, green and blue components of the color
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match self {
+            Color::Black => (0, 0, 0),
+            Color::Grey => (0x80, 0x80, 0x80),
+            Color::Red => (255, 0, 0),
+            Color::Green => (0, 255, 0),
+            Color::Yellow => (255, 255, 0),
+            Color::Blue => (0, 0, 255),
+            Color::Magenta => (255, 0, 255),
+            Color::Cyan => (0, 255, 255),
+            Color::White => (255, 255, 255),
+            Color::LightGreen => (0, 128, 0),
+            Color::LightRed => (128, 0, 0),
+            Color::LightBlue => (0, 0, 128),
+            Color::LightMagenta => (128, 0, 128),
+            Color::LightCyan => (0, 128, 128),
+            Color::LightYellow => (128, 128, 0),
+            Color::DarkGreen => (0, 64, 0),
+            Color::DarkRed => (64, 0, 0),
+            Color::DarkBlue => (0, 0, 64),
+            Color::DarkMagenta => (64, 0, 64),
+            Color::DarkCyan => (0, 64, 64),
+            Color::DarkYellow => (64, 64, 0),
+            Color::RGB(v) => *v,
+            Color::RGB32(v) | Color::RGBA32(v) => (
+                ((v >> 16) & 0xFF) as u8,
+                ((v >> 8) & 0xFF) as u8,
+                (v & 0xFF) as u8,
+            ),
+        }
+    }
+
+    // alpha returns the opacity of the color, 255 is opaque
+    pub fn alpha(&self) -> u8 {
+        match self {
+            Color::RGBA32(v) => (v >> 24) as u8,
+            _ => 255,
+        }
+    }
+
+    // blend mixes the color with other, t = 0 returns self, t = 255 other
+    pub fn blend(&self, other: Color, t: u8) -> Color {
+        let (r1, g1, b1) = self.rgb();
+        let (r2, g2, b2) = other.rgb();
+        let mix = |a: u8, b: u8| ((a as u32 * (255 - t as u32) + b as u32 * t as u32) / 255) as u8;
+        Color::RGB((mix(r1, r2), mix(g1, g2), mix(b1, b2)))
+    }
+
+    // lerp interpolates linearly between a and b, t = 0 returns a, t = 255 b
+    pub fn lerp(a: Color, b: Color, t: u8) -> Color {
+        let (r, g, b) = a.blend(b, t).rgb();
+        Color::RGB32((r as u32) << 16 | (g as u32) << 8 | b as u32)
+    }
+
+    // is_dark returns whether the luminance of the color (Rec. 601) is below
+    // the midpoint, so light text is readable on it
+    pub fn is_dark(&self) -> bool {
+        self.luminance() < 128
+    }
+
+    // luminance returns the gray value of the color (Rec. 601)
+    pub fn luminance(&self) -> u8 {
+        let (r, g, b) = self.rgb();
+        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+    }
+}
+
+// UnsupportedPixelFormat is returned for framebuffers pixel_bytes can't
+// produce pixels for
+#[derive(PartialEq, Debug)]
+pub struct UnsupportedPixelFormat {
+    pub pixel_format: PixelFormat,
+    pub bytes_per_pixel: usize,
+}
+
+// target returns the buffer drawing goes to: the draw buffer while double
+// buffering, the framebuffer otherwise. It takes the fields instead of the
+// display so the back buffer can be borrowed at the same time.
+fn target<'a>(
+    framebuffer: &'a mut Option<&'static mut [u8]>,
+    draw_buffer: &'a mut Option<Vec<u8>>,
+) -> &'a mut [u8] {
+    match draw_buffer.as_mut() {
+        Some(buffer) => buffer,
+        None => framebuffer.as_mut().unwrap(),
+    }
+}
+
+// edge_crossings returns the x coordinates where the edge from a to b crosses
+// the line at y: none if it doesn't, both ends if the edge is horizontal
+fn edge_crossings(a: (usize, usize), b: (usize, usize), y: usize) -> [Option<usize>; 2] {
+    let ((xa, ya), (xb, yb)) = if a.1 <= b.1 { (a, b) } else { (b, a) };
+    if y < ya || y > yb {
+        [None, None]
+    } else if ya == yb {
+        [Some(xa), Some(xb)]
+    } else {
+        let (xa, xb) = (xa as isize, xb as isize);
+        let x = xa + (xb - xa) * (y - ya) as isize / (yb - ya) as isize;
+        [Some(x as usize), None]
+    }
+}
+
+// glyph returns the 8x8 bitmap of c, from TINY_DIGITS if tiny is set,
+// else from the basic font or EXTRA_GLYPHS. Other characters are drawn as '?'.
+fn glyph(c: char, tiny: bool) -> [u8; 8] {
+    if let Some(digit) = c.to_digit(10).filter(|_| tiny) {
+        return TINY_DIGITS[digit as usize];
+    }
+    font8x8::BASIC_FONTS
+        .get(c)
+        .or_else(|| extra_glyph(c))
+        .or_else(|| font8x8::BASIC_FONTS.get('?'))
+        .unwrap()
+}
+
+// extra_glyph returns the bitmap of c from EXTRA_GLYPHS
+fn extra_glyph(c: char) -> Option<[u8; 8]> {
+    EXTRA_GLYPHS
+        .iter()
+        .find(|(extra, _)| *extra == c)
+        .map(|(_, bitmap)| *bitmap)
+}
+
+// encode_pixel returns the bytes representing color in a framebuffer with
+// the given pixel format
+fn encode_pixel(pixel_format: PixelFormat, color: Color) -> [u8; 4] {
+    let (r, g, b) = color.rgb();
+    match pixel_format {
+        PixelFormat::RGB => [r, g, b, 0],
+        PixelFormat::BGR => [b, g, r, 0],
+        PixelFormat::U8 => [color.luminance(), 0, 0, 0],
+        _ => [0; 4],
+    }
+}
+
+// FrameBuffer is an off-screen buffer in the pixel format of the display that
+// frames can be built in before they are copied to the screen with
+// Display::blit
+pub struct FrameBuffer {
+    data: Box<[u8]>,
+    info: FrameBufferInfo,
+}
+
+impl FrameBuffer {
+    pub fn width(&self) -> usize {
+        self.info.horizontal_resolution
+    }
+
+    pub fn height(&self) -> usize {
+        self.info.vertical_resolution
+    }
+
+    // write_pixel draws a pixel like Display::write_pixel, translucent colors
+    // are drawn opaque
+    pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
+        let x = x % self.info.horizontal_resolution;
+        let y = y % self.info.vertical_resolution;
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let offset = (y * self.info.stride + x) * bytes_per_pixel;
+        let color = encode_pixel(self.info.pixel_format, color);
+        self.data[offset..offset + bytes_per_pixel].copy_from_slice(&color[..bytes_per_pixel]);
+    }
+}
+
+// embolden returns the font row byte, merged with itself shifted one pixel to
+// the right if bold is set
+fn embolden(byte: u8, bold: bool) -> u16 {
+    let row = byte as u16;
+    if bold {
+        row | row << 1
+    } else {
+        row
+    }
+}
+
+// for_each_circle_octant calls f with the offsets (dx, dy) of the points of
+// one octant of a circle with the midpoint circle algorithm, dx >= dy. The
+// other octants follow by symmetry.
+fn for_each_circle_octant(radius: usize, mut f: impl FnMut(isize, isize)) {
+    let mut x = radius as isize;
+    let mut y = 0;
+    let mut err = 1 - x;
+    while x >= y {
+        f(x, y);
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+// for_each_ellipse_quadrant calls f with the offsets (dx, dy) of the points of
+// one quadrant of an ellipse with the midpoint ellipse algorithm. The other
+// quadrants follow by symmetry. The decision values are scaled by 4 to stay
+// in integers.
+fn for_each_ellipse_quadrant(rx: usize, ry: usize, mut f: impl FnMut(isize, isize)) {
+    if ry == 0 {
+        for x in 0..=rx as isize {
+            f(x, 0);
+        }
+        return;
+    }
+    let (rx2, ry2) = ((rx * rx) as i64, (ry * ry) as i64);
+    let (mut x, mut y) = (0i64, ry as i64);
+    let (mut dx, mut dy) = (0, 2 * rx2 * y);
+
+    // region 1, the slope is flatter than -1: step x, sometimes y
+    let mut d = 4 * ry2 - 4 * rx2 * y + rx2;
+    while dx < dy {
+        f(x as isize, y as isize);
+        x += 1;
+        dx += 2 * ry2;
+        if d < 0 {
+            d += 4 * (dx + ry2);
+        } else {
+            y -= 1;
+            dy -= 2 * rx2;
+            d += 4 * (dx - dy + ry2);
+        }
+    }
+
+    // region 2, the slope is steeper than -1: step y, sometimes x
+    let mut d = ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2;
+    while y >= 0 {
+        f(x as isize, y as isize);
+        y -= 1;
+        dy -= 2 * rx2;
+        if d > 0 {
+            d += 4 * (rx2 - dy);
+        } else {
+            x += 1;
+            dx += 2 * ry2;
+            d += 4 * (dx - dy + rx2);
+        }
+    }
+}
+
+// check_pixel_format returns an error unless pixel_bytes supports the format
+// and pixel size of info
+fn check_pixel_format(info: &FrameBufferInfo) -> Result<(), UnsupportedPixelFormat> {
+    let supported = match info.pixel_format {
+        PixelFormat::RGB | PixelFormat::BGR => (3..=4).contains(&info.bytes_per_pixel),
+        PixelFormat::U8 => (1..=4).contains(&info.bytes_per_pixel),
+        _ => false,
+    };
+    if supported {
+        Ok(())
+    } else {
+        Err(UnsupportedPixelFormat {
+            pixel_format: info.pixel_format,
+            bytes_per_pixel: info.bytes_per_pixel,
+        })
+    }
+}
+
+pub struct Display {
+    framebuffer: Option<&'static mut [u8]>,
+    // back_buffer has the layout of the framebuffer, see enable_back_buffer
+    back_buffer: Option<Vec<u8>>,
+    // draw_buffer receives all drawing while double buffering, see flip
+    draw_buffer: Option<Vec<u8>>,
+    pub info: Option<FrameBufferInfo>,
+    pub x_pos: usize,
+    pub y_pos: usize,
+    pub color: Color,
+    pub background_color: Color,
+    min_frame_ticks: u64,
+    last_frame_tick: u64,
+    current_tick: u64,
+    // fade_level is the brightness flip shows the draw buffer with, see
+    // animate_fade_in
+    fade_level: u8,
+    // clip is the (x, y, w, h) rectangle drawing is limited to, see
+    // set_clip_rect
+    clip: Option<(usize, usize, usize, usize)>,
+    // font_scale is the size of a font pixel on screen, see set_font_scale
+    font_scale: usize,
+    // bold text is drawn twice, the second time one font pixel to the right
+    bold: bool,
+    // underlined text has a line in the foreground color below it
+    underline: bool,
+    // the tiny font replaces the digits with TINY_DIGITS, see set_tiny_font
+    tiny: bool,
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Display {
+            framebuffer: None,
+            back_buffer: None,
+            draw_buffer: None,
+            info: None,
+            x_pos: 0,
+            y_pos: 0,
+            color: Color::Green,
+            background_color: Color::Black,
+            min_frame_ticks: 0,
+            last_frame_tick: 0,
+            current_tick: 0,
+            fade_level: 255,
+            clip: None,
+            font_scale: 1,
+            bold: false,
+            underline: false,
+            tiny: false,
+        }
+    }
+
+    // set_fps_target limits how often frame_due allows a frame to be drawn,
+    // 0 removes the limit
+    pub fn set_fps_target(&mut self, fps: u32) {
+        self.min_frame_ticks = TICKS_PER_SECOND.checked_div(fps).unwrap_or(0) as u64;
+    }
+
+    // tick advances the display clock, call it once per timer tick
+    pub fn tick(&mut self) {
+        self.current_tick += 1;
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    // frame_due returns whether enough ticks have elapsed since the last
+    // frame to draw a new one and if so marks the current tick as drawn
+    pub fn frame_due(&mut self) -> bool {
+        if self.current_tick - self.last_frame_tick < self.min_frame_ticks {
+            return false;
+        }
+        self.last_frame_tick = self.current_tick;
+        true
+    }
+
+    // new_for_test returns a display backed by a heap allocated RGB framebuffer
+    #[cfg(test)]
+    pub fn new_for_test(width: usize, height: usize) -> Self {
+        let info = FrameBufferInfo {
+            byte_len: width * height * 4,
+            horizontal_resolution: width,
+            vertical_resolution: height,
+            pixel_format: PixelFormat::RGB,
+            bytes_per_pixel: 4,
+            stride: width,
+        };
+        let mut display = Display::new();
+        display.framebuffer = Some(alloc::vec![0; info.byte_len].leak());
+        display.info = Some(info);
+        display
+    }
+
+    // set_framebuffer makes the display draw to framebuffer and fails if its
+    // pixel format is not supported
+    pub fn set_framebuffer(
+        &mut self,
+        framebuffer: &'static mut bootloader::boot_info::FrameBuffer,
+    ) -> Result<(), UnsupportedPixelFormat> {
+        let previous = self.info.replace(framebuffer.info());
+        if let Err(err) = self.validate_pixel_format() {
+            self.info = previous;
+            return Err(err);
+        }
+        self.framebuffer = Some(framebuffer.buffer_mut());
+        Ok(())
+    }
+
+    // validate_pixel_format returns an error unless the pixel format of the
+    // framebuffer is fully supported
+    pub fn validate_pixel_format(&self) -> Result<(), UnsupportedPixelFormat> {
+        check_pixel_format(&self.info.unwrap())
+    }
+
+    // enable_back_buffer allocates a buffer the size of the framebuffer to save
+    // parts of the screen to. It returns false if the heap is too small.
+    pub fn enable_back_buffer(&mut self) -> bool {
+        if self.back_buffer.is_none() {
+            let mut buffer = Vec::new();
+            if buffer
+                .try_reserve_exact(self.info.unwrap().byte_len)
+                .is_err()
+            {
+                return false;
+            }
+            buffer.resize(self.info.unwrap().byte_len, 0);
+            self.back_buffer = Some(buffer);
+        }
+        true
+    }
+
+    pub fn has_back_buffer(&self) -> bool {
+        self.back_buffer.is_some()
+    }
+
+    // enable_double_buffering allocates a buffer the size of the framebuffer
+    // that all drawing goes to until flip copies it to the screen. It returns
+    // false if the heap is too small.
+    pub fn enable_double_buffering(&mut self) -> bool {
+        if self.draw_buffer.is_none() {
+            let mut buffer = Vec::new();
+            if buffer
+                .try_reserve_exact(self.info.unwrap().byte_len)
+                .is_err()
+            {
+                return false;
+            }
+            buffer.extend_from_slice(self.framebuffer.as_ref().unwrap());
+            self.draw_buffer = Some(buffer);
+        }
+        true
+    }
+
+    // flip copies the draw buffer to the framebuffer, scaled by the fade
+    // level during a fade in. Without double buffering it does nothing.
+    pub fn flip(&mut self) {
+        if let Some(draw_buffer) = self.draw_buffer.as_ref() {
+            let framebuffer = self.framebuffer.as_mut().unwrap();
+            if self.fade_level == 255 {
+                framebuffer.copy_from_slice(draw_buffer);
+            } else {
+                for (dst, src) in framebuffer.iter_mut().zip(draw_buffer.iter()) {
+                    *dst = scale_component(*src, self.fade_level);
+                }
+            }
+            let _ = unsafe { ptr::read_volatile(&framebuffer[0]) };
+        }
+    }
+
+    // target_ref returns the buffer drawing goes to
+    fn target_ref(&self) -> &[u8] {
+        match self.draw_buffer.as_ref() {
+            Some(buffer) => buffer,
+            None => self.framebuffer.as_ref().unwrap(),
+        }
+    }
+
+    // copy_rect_to_back copies a rectangle of the screen to the back buffer
+    pub fn copy_rect_to_back(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let info = self.info.unwrap();
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        let back_buffer = self.back_buffer.as_mut().expect("back buffer not enabled");
+        for range in rect_rows(&info, x, y, width, height) {
+            back_buffer[range.clone()].copy_from_slice(&framebuffer[range]);
+        }
+    }
+
+    // copy_rect_from_back restores a rectangle of the screen from the back
+    // buffer
+    pub fn copy_rect_from_back(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let info = self.info.unwrap();
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        let back_buffer = self.back_buffer.as_ref().expect("back buffer not enabled");
+        for range in rect_rows(&info, x, y, width, height) {
+            let start = range.start;
+            framebuffer[range.clone()].copy_from_slice(&back_buffer[range]);
+            let _ = unsafe { ptr::read_volatile(&framebuffer[start]) };
+        }
+    }
+
+    // snapshot_region returns a copy of the framebuffer bytes of a rectangle,
+    // clipped to the screen
+    pub fn snapshot_region(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<u8> {
+        let info = self.info.unwrap();
+        let framebuffer = self.target_ref();
+        let mut snapshot = Vec::new();
+        for range in rect_rows(&info, x, y, w, h) {
+            snapshot.extend_from_slice(&framebuffer[range]);
+        }
+        snapshot
+    }
+
+    // restore_region writes a snapshot taken with snapshot_region with the same
+    // rectangle back to the framebuffer
+    pub fn restore_region(&mut self, x: usize, y: usize, w: usize, h: usize, snapshot: &[u8]) {
+        let info = self.info.unwrap();
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        let mut offset = 0;
+        for range in rect_rows(&info, x, y, w, h) {
+            let len = range.len();
+            let start = range.start;
+            framebuffer[range].copy_from_slice(&snapshot[offset..offset + len]);
+            let _ = unsafe { ptr::read_volatile(&framebuffer[start]) };
+            offset += len;
+        }
+        assert_eq!(offset, snapshot.len(), "snapshot doesn't match the region");
+    }
+
+    pub fn set_xy(&mut self, x: usize, y: usize) {
+        self.x_pos = x;
+        self.y_pos = y;
+    }
+
+    // draw_char_matrix writes lines as a block of text with its top left corner
+    // at (x, y)
+    pub fn draw_char_matrix(&mut self, x: usize, y: usize, lines: &[&str], fg: Color, bg: Color) {
+        for (i, line) in lines.iter().enumerate() {
+            self.set_xy(x, y + i * self.line_height());
+            for c in line.chars() {
+                self.write_char_colored(c, fg, bg);
+            }
+        }
+    }
+
+    fn newline(&mut self) {
+        self.y_pos += self.line_height();
+        self.carriage_return()
+    }
+
+    fn carriage_return(&mut self) {
+        self.x_pos = 0;
+    }
+
+    // scroll_up moves the screen content up by pixels rows, clears the band
+    // that becomes free at the bottom and moves the cursor up with it
+    pub fn scroll_up(&mut self, pixels: usize) {
+        let info = self.info.unwrap();
+        let rows = pixels.min(info.vertical_resolution);
+        let row_bytes = info.stride * info.bytes_per_pixel;
+        let len = info.vertical_resolution * row_bytes;
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        framebuffer.copy_within(rows * row_bytes..len, 0);
+        framebuffer[len - rows * row_bytes..len].fill(0);
+        let _ = unsafe { ptr::read_volatile(&framebuffer[0]) };
+        self.y_pos = self.y_pos.saturating_sub(rows);
+    }
+
+    /// Erases all text on the screen.
+    pub fn clear(&mut self) {
+        self.x_pos = 0;
+        self.y_pos = 0;
+        target(&mut self.framebuffer, &mut self.draw_buffer).fill(0);
+    }
+
+    // set_clip_rect limits all drawing except clear and scrolling to the
+    // rectangle at (x, y) of size w x h until clear_clip is called
+    pub fn set_clip_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.clip = Some((x, y, w, h));
+    }
+
+    // clear_clip allows drawing on the whole screen again
+    pub fn clear_clip(&mut self) {
+        self.clip = None;
+    }
+
+    // clip_bounds returns the start and end, exclusive, of the area drawing
+    // is allowed in: the clip rect intersected with the screen
+    fn clip_bounds(&self) -> (usize, usize, usize, usize) {
+        let (width, height) = (self.width(), self.height());
+        match self.clip {
+            Some((x, y, w, h)) => (
+                x.min(width),
+                y.min(height),
+                x.saturating_add(w).min(width),
+                y.saturating_add(h).min(height),
+            ),
+            None => (0, 0, width, height),
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.info.unwrap().horizontal_resolution
+    }
+
+    fn height(&self) -> usize {
+        self.info.unwrap().vertical_resolution
+    }
+
+    pub fn write_char_colored(&mut self, c: char, fg: Color, bg: Color) {
+        match c {
+            '\n' => self.newline(),
+            '\r' => self.carriage_return(),
+            c => {
+                if self.x_pos >= self.width() {
+                    self.newline();
+                }
+                while self.y_pos >= (self.height() - 8 * self.font_scale) {
+                    self.scroll_up(self.line_height());
+                }
+                self.write_rendered_char(c, glyph(c, self.tiny), fg, bg);
+            }
+        }
+    }
+
+    // write_rendered_char draws every font pixel of rendered_char as a
+    // font_scale x font_scale square
+    fn write_rendered_char(&mut self, c: char, rendered_char: [u8; 8], fg: Color, bg: Color) {
+        let scale = self.font_scale;
+        let width = self.glyph_width(c);
+        for (y, byte) in rendered_char.iter().enumerate() {
+            let row = embolden(*byte, self.bold);
+            for bit in 0..width / scale {
+                let color = if row & (1 << bit) == 0 { bg } else { fg };
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (x, y) = (bit * scale + dx, y * scale + dy);
+                        self.write_pixel(self.x_pos + x, self.y_pos + y, color);
+                    }
+                }
+            }
+        }
+        if self.underline {
+            let y = self.y_pos + 8 * scale;
+            self.draw_horizontal_line(y, self.x_pos, self.x_pos + width, fg);
+        }
+        self.x_pos += width;
+    }
+
+    // set_font_scale sets the size of text written from now on to scale
+    // times the 8x8 font, scale is 1, 2 or 4
+    pub fn set_font_scale(&mut self, scale: usize) {
+        assert!(
+            matches!(scale, 1 | 2 | 4),
+            "unsupported font scale {}",
+            scale
+        );
+        self.font_scale = scale;
+    }
+
+    // set_bold makes text written from now on bold. Bold glyphs are one font
+    // pixel wider.
+    pub fn set_bold(&mut self, enabled: bool) {
+        self.bold = enabled;
+    }
+
+    // set_underline underlines text written from now on, in the line of
+    // pixels below the glyphs
+    pub fn set_underline(&mut self, enabled: bool) {
+        self.underline = enabled;
+    }
+
+    // set_tiny_font makes digits written from now on TINY_DIGITS, all other
+    // characters keep the basic font
+    #[allow(dead_code)]
+    pub fn set_tiny_font(&mut self, enabled: bool) {
+        self.tiny = enabled;
+    }
+
+    // line_height returns the distance between two lines of text in pixels
+    fn line_height(&self) -> usize {
+        8 * self.font_scale + LINE_SPACING
+    }
+
+    // glyph_width returns the horizontal advance of c in pixels, 5 for tiny
+    // digits, 4 for extra glyphs and 8 for the basic font, scaled by the font
+    // scale
+    pub fn glyph_width(&self, c: char) -> usize {
+        let width = match c {
+            '\n' | '\r' => return 0,
+            '0'..='9' if self.tiny => TINY_GLYPH_WIDTH,
+            c if font8x8::BASIC_FONTS.get(c).is_none() && extra_glyph(c).is_some() => {
+                EXTRA_GLYPH_WIDTH
+            }
+            _ => 8,
+        };
+        (width + self.bold as usize) * self.font_scale
+    }
+
+    // str_pixel_width returns the width of s in pixels when written on one
+    // line
+    pub fn str_pixel_width(&self, s: &str) -> usize {
+        s.chars().map(|c| self.glyph_width(c)).sum()
+    }
+
+    // measure_text returns the width of the longest line of s and the height
+    // of all its lines in pixels
+    pub fn measure_text(&self, s: &str) -> (usize, usize) {
+        let width = s
+            .split('\n')
+            .map(|line| self.str_pixel_width(line))
+            .max()
+            .unwrap_or(0);
+        (width, s.split('\n').count() * self.line_height())
+    }
+
+    // measure_chars is measure_text for chars, e.g. from a CharsBuf
+    pub fn measure_chars(&self, chars: &[char]) -> (usize, usize) {
+        self.measure(chars.iter().copied())
+    }
+
+    // measure adds up the glyph widths of each line of chars
+    fn measure(&self, chars: impl Iterator<Item = char>) -> (usize, usize) {
+        let (mut width, mut line_width, mut lines) = (0, 0, 1);
+        for c in chars {
+            if c == '\n' {
+                line_width = 0;
+                lines += 1;
+            } else {
+                line_width += self.glyph_width(c);
+                width = width.max(line_width);
+            }
+        }
+        (width, lines * self.line_height())
+    }
+
+    // write_str_centered writes s horizontally centered on the line at y
+    pub fn write_str_centered(&mut self, s: &str, y: usize) {
+        let (width, _) = self.measure_text(s);
+        self.set_xy(self.width().saturating_sub(width) / 2, y);
+        for c in s.chars() {
+            self.write_char_colored(c, self.color, self.background_color);
+        }
+    }
+
+    // draw_text_at writes chars starting at (x, y)
+    pub fn draw_text_at(&mut self, x: usize, y: usize, chars: &[char], fg: Color, bg: Color) {
+        self.set_xy(x, y);
+        for c in chars {
+            self.write_char_colored(*c, fg, bg);
+        }
+    }
+
+    // write_chars_centered writes chars in the color fg horizontally centered
+    // on the line at y
+    pub fn write_chars_centered(&mut self, chars: &[char], y: usize, fg: Color) {
+        let (width, _) = self.measure_chars(chars);
+        self.set_xy(self.width().saturating_sub(width) / 2, y);
+        for c in chars {
+            self.write_char_colored(*c, fg, self.background_color);
+        }
+    }
+
+    // draw_number_large writes n with LARGE_DIGITS at (x, y), the unset
+    // pixels of the digits are drawn in the background color. Bold digits
+    // are one pixel wider.
+    pub fn draw_number_large(&mut self, x: usize, y: usize, n: usize, color: Color) {
+        let mut buf = [0; 20];
+        let width = 8 + self.bold as usize;
+        for (i, digit) in decimal_digits(n, &mut buf).iter().enumerate() {
+            let glyph = &LARGE_DIGITS[*digit as usize];
+            for (row, byte) in glyph.iter().enumerate() {
+                let byte = embolden(*byte, self.bold);
+                for bit in 0..width {
+                    let c = if byte & (1 << bit) == 0 {
+                        self.background_color
+                    } else {
+                        color
+                    };
+                    self.write_pixel(x + i * (width + 1) + bit, y + row, c);
+                }
+            }
+        }
+    }
+
+    // animate_fade_in fades the screen in from black over duration_ticks,
+    // starting at start_tick. It sets the level the next flip scales every
+    // byte of the draw buffer by, which costs a full pass over the
+    // framebuffer per flip during the fade. The draw buffer itself stays
+    // untouched, so anything drawn during the fade shows up faded too.
+    // Without double buffering the screen is left as it is.
+    pub fn animate_fade_in(&mut self, current_tick: u64, start_tick: u64, duration_ticks: u64) {
+        if self.draw_buffer.is_none() || current_tick < start_tick {
+            return;
+        }
+        self.fade_level = fade_level(current_tick - start_tick, duration_ticks);
+    }
+
+    // print_boot_logo draws the boot logo horizontally centered at the current
+    // line and moves the cursor below it. The logo is built off-screen and
+    // blitted in one go.
+    pub fn print_boot_logo(&mut self) {
+        let (w, h) = (64 * LOGO_SCALE, BOOT_LOGO.len() * LOGO_SCALE);
+        let mut logo = self.create_buffer(w, h);
+        for y in 0..h {
+            for x in 0..w {
+                let (bit, column) = (x / LOGO_SCALE, x / LOGO_SCALE / 8);
+                let color = if BOOT_LOGO[y / LOGO_SCALE][column] & (1 << (bit % 8)) == 0 {
+                    Color::Black
+                } else {
+                    Color::Green
+                };
+                logo.write_pixel(x, y, color);
+            }
+        }
+        let x_start = self.width().saturating_sub(logo.width()) / 2;
+        let size = (logo.width(), logo.height());
+        self.blit(&logo, (0, 0), (x_start, self.y_pos), size);
+        self.y_pos += BOOT_LOGO.len() * LOGO_SCALE + LINE_SPACING;
+        self.carriage_return();
+    }
+
+    pub fn write_pixel(&mut self, mut x: usize, mut y: usize, color: Color) {
+        if let Some((clip_x, clip_y, clip_w, clip_h)) = self.clip {
+            if x < clip_x || y < clip_y || x - clip_x >= clip_w || y - clip_y >= clip_h {
+                return;
+            }
+        }
+        x = x % self.info.unwrap().horizontal_resolution;
+        y = y % self.info.unwrap().vertical_resolution;
+        let pixel_offset = y * self.info.unwrap().stride + x;
+        let bytes_per_pixel = self.info.unwrap().bytes_per_pixel;
+        let byte_offset = pixel_offset * bytes_per_pixel;
+
+        // only translucent colors need the pixel below, reading back from
+        // video memory is slow
+        let alpha = color.alpha();
+        let dst = if alpha < 255 {
+            self.pixel_color(&self.target_ref()[byte_offset..])
+        } else {
+            None
+        };
+        let color = match dst {
+            Some(dst) => self.pixel_bytes(color.blend(dst, 255 - alpha)),
+            None => self.pixel_bytes(color),
+        };
+
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        framebuffer[byte_offset..(byte_offset + bytes_per_pixel)]
+            .copy_from_slice(&color[..bytes_per_pixel]);
+        let _ = unsafe { ptr::read_volatile(&framebuffer[byte_offset]) };
+    }
+
+    // pixel_color returns the color of the pixel starting at bytes, or None
+    // for U8 pixels, which are drawn opaque
+    fn pixel_color(&self, bytes: &[u8]) -> Option<Color> {
+        match self.info.unwrap().pixel_format {
+            PixelFormat::RGB => Some(Color::RGB((bytes[0], bytes[1], bytes[2]))),
+            PixelFormat::BGR => Some(Color::RGB((bytes[2], bytes[1], bytes[0]))),
+            _ => None,
+        }
+    }
+
+    // pixel_bytes returns the bytes representing color in the framebuffer
+    fn pixel_bytes(&self, color: Color) -> [u8; 4] {
+        encode_pixel(self.info.unwrap().pixel_format, color)
+    }
+
+    // create_buffer returns an off-screen buffer of w x h pixels in the pixel
+    // format of the display, see blit
+    pub fn create_buffer(&self, w: usize, h: usize) -> FrameBuffer {
+        let info = self.info.unwrap();
+        let info = FrameBufferInfo {
+            byte_len: w * h * info.bytes_per_pixel,
+            horizontal_resolution: w,
+            vertical_resolution: h,
+            pixel_format: info.pixel_format,
+            bytes_per_pixel: info.bytes_per_pixel,
+            stride: w,
+        };
+        FrameBuffer {
+            data: alloc::vec![0; info.byte_len].into_boxed_slice(),
+            info,
+        }
+    }
+
+    // blit copies the rectangle of the given (w, h) size at the (x, y)
+    // position src of buf to dst on the screen, clipped to both and to the
+    // clip rect
+    pub fn blit(
+        &mut self,
+        buf: &FrameBuffer,
+        src: (usize, usize),
+        dst: (usize, usize),
+        size: (usize, usize),
+    ) {
+        let ((mut src_x, mut src_y), (mut dst_x, mut dst_y), (w, h)) = (src, dst, size);
+        let (min_x, min_y, max_x, max_y) = self.clip_bounds();
+        let skip_x = min_x.saturating_sub(dst_x);
+        let skip_y = min_y.saturating_sub(dst_y);
+        src_x += skip_x;
+        dst_x += skip_x;
+        src_y += skip_y;
+        dst_y += skip_y;
+        let w = w
+            .saturating_sub(skip_x)
+            .min(buf.info.horizontal_resolution.saturating_sub(src_x))
+            .min(max_x.saturating_sub(dst_x));
+        let h = h
+            .saturating_sub(skip_y)
+            .min(buf.info.vertical_resolution.saturating_sub(src_y))
+            .min(max_y.saturating_sub(dst_y));
+        let info = self.info.unwrap();
+        let src_rows = rect_rows(&buf.info, src_x, src_y, w, h);
+        let dst_rows = rect_rows(&info, dst_x, dst_y, w, h);
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        for (src, dst) in src_rows.zip(dst_rows) {
+            let start = dst.start;
+            framebuffer[dst].copy_from_slice(&buf.data[src]);
+            let _ = unsafe { ptr::read_volatile(&framebuffer[start]) };
+        }
+    }
+
+    // blit_bmp draws img with its top left corner at (x, y), clipped to the
+    // screen and the clip rect
+    #[allow(dead_code)]
+    pub fn blit_bmp(&mut self, img: &bmp::BmpImage, x: usize, y: usize) {
+        let (_, _, max_x, max_y) = self.clip_bounds();
+        let width = img.width.min(max_x.saturating_sub(x));
+        let height = img.height.min(max_y.saturating_sub(y));
+        for row in 0..height {
+            for col in 0..width {
+                self.write_pixel(x + col, y + row, img.pixel(col, row));
+            }
+        }
+    }
+
+    // draw_bitmap draws a 1-bit bitmap into the (x, y, bw, bh) rectangle
+    // rect, set bits in fg and clear bits in bg. Each row starts at a new
+    // byte, the most significant bit is the leftmost pixel.
+    pub fn draw_bitmap(
+        &mut self,
+        rect: (usize, usize, usize, usize),
+        bitmap: &[u8],
+        fg: Color,
+        bg: Color,
+    ) {
+        let (x, y, bw, bh) = rect;
+        let row_bytes = (bw + 7) / 8;
+        for (row, bytes) in bitmap.chunks(row_bytes).take(bh).enumerate() {
+            for col in 0..bw {
+                let set = bytes[col / 8] & (0x80 >> (col % 8)) != 0;
+                self.write_pixel(x + col, y + row, if set { fg } else { bg });
+            }
+        }
+    }
+
+    // write_block draws a square block of the specified color at the specified
+    pub fn write_block(&mut self, x: usize, y: usize, color: Color) {
+        for i in 0..BLOCK_SIZE {
+            for j in 0..BLOCK_SIZE {
+                self.write_pixel(x + i, y + j, color);
+            }
+        }
+    }
+
+    // write_block_colored_border draws a block filled with fill and a one
+    // pixel border of color border
+    pub fn write_block_colored_border(&mut self, x: usize, y: usize, fill: Color, border: Color) {
+        self.draw_rect(x + 1, y + 1, BLOCK_SIZE - 2, BLOCK_SIZE - 2, fill);
+        self.draw_rect_border(x, y, BLOCK_SIZE, BLOCK_SIZE, border);
+    }
+
+    // write_block_row draws blocks of the specified color at all xs in the
+    // row of blocks starting at y. The rows are filled in a single top to
+    // bottom pass so the framebuffer is written sequentially.
+    #[allow(dead_code)]
+    pub fn write_block_row(&mut self, y: usize, xs: &[usize], color: Color) {
+        let sorted;
+        let xs = if xs.windows(2).all(|w| w[0] <= w[1]) {
+            xs
+        } else {
+            let mut v = xs.to_vec();
+            v.sort_unstable();
+            sorted = v;
+            &sorted[..]
+        };
+
+        if self.clip.is_some()
+            || y + BLOCK_SIZE > self.height()
+            || xs.iter().any(|x| x + BLOCK_SIZE > self.width())
+        {
+            // partially off screen or clipped, leave it to write_pixel
+            for x in xs {
+                self.write_block(*x, y, color);
+            }
+            return;
+        }
+
+        let color = self.pixel_bytes(color);
+        let info = self.info.unwrap();
+        let bytes_per_pixel = info.bytes_per_pixel;
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        for row in y..y + BLOCK_SIZE {
+            for x in xs {
+                let start = (row * info.stride + x) * bytes_per_pixel;
+                framebuffer[start..start + BLOCK_SIZE * bytes_per_pixel]
+                    .chunks_exact_mut(bytes_per_pixel)
+                    .for_each(|p| p.copy_from_slice(&color[..bytes_per_pixel]));
+            }
+        }
+        let _ = unsafe { ptr::read_volatile(&framebuffer[y * info.stride * bytes_per_pixel]) };
+    }
+
+    // draw_vertical_gradient_line draws the pixels from y0 up to y1 in
+    // column x, fading from top to bottom
+    pub fn draw_vertical_gradient_line(
+        &mut self,
+        x: usize,
+        y0: usize,
+        y1: usize,
+        top: Color,
+        bottom: Color,
+    ) {
+        if y1 <= y0 {
+            return;
+        }
+        let steps = core::cmp::max(y1 - y0 - 1, 1);
+        for y in y0..y1 {
+            let t = ((y - y0) * 255 / steps) as u8;
+            self.write_pixel(x, y, top.blend(bottom, t));
+        }
+    }
+
+    // draw_gradient_rect_v draws a rectangle fading from top to bottom
+    pub fn draw_gradient_rect_v(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        top: Color,
+        bottom: Color,
+    ) {
+        for column in x..x + w {
+            self.draw_vertical_gradient_line(column, y, y + h, top, bottom);
+        }
+    }
+
+    // draw_gradient_rect draws the (x, y, w, h) rectangle rect fading from
+    // start to end, from left to right if horizontal is set, from top to
+    // bottom otherwise. Every column or row has a single color.
+    pub fn draw_gradient_rect(
+        &mut self,
+        rect: (usize, usize, usize, usize),
+        start: Color,
+        end: Color,
+        horizontal: bool,
+    ) {
+        let (x, y, w, h) = rect;
+        let len = if horizontal { w } else { h };
+        let steps = core::cmp::max(len.saturating_sub(1), 1);
+        for i in 0..len {
+            let color = Color::lerp(start, end, (i * 255 / steps) as u8);
+            if horizontal {
+                self.draw_vertical_line(x + i, y, y + h, color);
+            } else {
+                self.draw_horizontal_line(y + i, x, x + w, color);
+            }
+        }
+    }
+
+    // draw_border draws a border around the screen with a one block padding
+    #[allow(dead_code)]
+    pub fn draw_borders(&mut self) {
+        self.draw_borders_with_color(Color::DarkGreen);
+    }
+
+    // draw_borders_with_color is draw_borders in the given color
+    pub fn draw_borders_with_color(&mut self, color: Color) {
+        self.draw_rect_outline(
+            BLOCK_SIZE,
+            BLOCK_SIZE,
+            self.width() - 2 * BLOCK_SIZE,
+            self.height() - 2 * BLOCK_SIZE,
+            color,
+            BLOCK_SIZE,
+        );
+    }
+
+    // draw_horizontal_line draws a one pixel high line at y from x_start up to
+    // but excluding x_end
+    pub fn draw_horizontal_line(&mut self, y: usize, x_start: usize, x_end: usize, color: Color) {
+        if x_start < x_end {
+            self.draw_rect(x_start, y, x_end - x_start, 1, color);
+        }
+    }
+
+    // draw_vertical_line draws a one pixel wide line at x from y_start up to
+    // but excluding y_end
+    pub fn draw_vertical_line(&mut self, x: usize, y_start: usize, y_end: usize, color: Color) {
+        if y_start < y_end {
+            self.draw_rect(x, y_start, 1, y_end - y_start, color);
+        }
+    }
+
+    // draw_line draws a line from (x0, y0) to (x1, y1), both ends included,
+    // with Bresenham's algorithm
+    #[allow(dead_code)]
+    pub fn draw_line(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Color) {
+        let (mut x, mut y) = (x0 as isize, y0 as isize);
+        let (x1, y1) = (x1 as isize, y1 as isize);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.write_pixel(x as usize, y as usize, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // draw_circle draws the outline of a circle around (cx, cy) with the
+    // midpoint circle algorithm. Pixels left or above the screen are skipped.
+    #[allow(dead_code)]
+    pub fn draw_circle(&mut self, cx: usize, cy: usize, radius: usize, color: Color) {
+        for_each_circle_octant(radius, |dx, dy| {
+            for (px, py) in [(dx, dy), (dy, dx)].iter() {
+                for (sx, sy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)].iter() {
+                    let x = cx as isize + sx * px;
+                    let y = cy as isize + sy * py;
+                    if x >= 0 && y >= 0 {
+                        self.write_pixel(x as usize, y as usize, color);
+                    }
+                }
+            }
+        });
+    }
+
+    // draw_filled_circle draws a filled circle around (cx, cy), row by row
+    #[allow(dead_code)]
+    pub fn draw_filled_circle(&mut self, cx: usize, cy: usize, radius: usize, color: Color) {
+        for_each_circle_octant(radius, |dx, dy| {
+            for (half_width, dy) in [(dx, dy), (dy, dx)].iter() {
+                for y in [cy as isize - dy, cy as isize + dy].iter() {
+                    if *y < 0 {
+                        continue;
+                    }
+                    let start = (cx as isize - half_width).max(0) as usize;
+                    let end = cx + *half_width as usize;
+                    self.draw_rect(start, *y as usize, end - start + 1, 1, color);
+                }
+            }
+        });
+    }
+
+    // draw_ellipse draws the outline of an ellipse around (cx, cy) with the
+    // radii rx and ry, using the midpoint ellipse algorithm. Pixels left or
+    // above the screen are skipped.
+    #[allow(dead_code)]
+    pub fn draw_ellipse(&mut self, cx: usize, cy: usize, rx: usize, ry: usize, color: Color) {
+        for_each_ellipse_quadrant(rx, ry, |dx, dy| {
+            for (sx, sy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)].iter() {
+                let x = cx as isize + sx * dx;
+                let y = cy as isize + sy * dy;
+                if x >= 0 && y >= 0 {
+                    self.write_pixel(x as usize, y as usize, color);
+                }
+            }
+        });
+    }
+
+    // draw_filled_ellipse draws a filled ellipse around (cx, cy), row by row
+    #[allow(dead_code)]
+    pub fn draw_filled_ellipse(
+        &mut self,
+        cx: usize,
+        cy: usize,
+        rx: usize,
+        ry: usize,
+        color: Color,
+    ) {
+        for_each_ellipse_quadrant(rx, ry, |half_width, dy| {
+            for y in [cy as isize - dy, cy as isize + dy].iter() {
+                if *y < 0 {
+                    continue;
+                }
+                let start = (cx as isize - half_width).max(0) as usize;
+                let end = cx + half_width as usize;
+                self.draw_rect(start, *y as usize, end - start + 1, 1, color);
+            }
+        });
+    }
+
+    // draw_triangle draws a filled triangle with the (x, y) corners a, b and c
+    // line by line. Each line spans the points where it crosses the edges of
+    // the triangle.
+    #[allow(dead_code)]
+    pub fn draw_triangle(
+        &mut self,
+        a: (usize, usize),
+        b: (usize, usize),
+        c: (usize, usize),
+        color: Color,
+    ) {
+        let points = [a, b, c];
+        let top = a.1.min(b.1).min(c.1);
+        let bottom = a.1.max(b.1).max(c.1);
+        for y in top..=bottom.min(self.height().saturating_sub(1)) {
+            let mut span: Option<(usize, usize)> = None;
+            for i in 0..3 {
+                let (a, b) = (points[i], points[(i + 1) % 3]);
+                for x in edge_crossings(a, b, y).iter().flatten() {
+                    span = Some(match span {
+                        Some((start, end)) => (start.min(*x), end.max(*x)),
+                        None => (*x, *x),
+                    });
+                }
+            }
+            if let Some((start, end)) = span {
+                self.draw_horizontal_line(y, start, end + 1, color);
+            }
+        }
+    }
+
+    // draw_rounded_rect draws a filled rectangle with quarter circles of
+    // radius as corners. radius is clamped to half the shorter side.
+    pub fn draw_rounded_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        radius: usize,
+        color: Color,
+    ) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        let r = radius.min(w.min(h) / 2);
+        self.draw_rect(x, y + r, w, h - 2 * r, color);
+        // the corner circles are centered this far inside the rectangle
+        let (left, right) = (x + r, x + w - 1 - r);
+        let (top, bottom) = (y + r, y + h - 1 - r);
+        // collect the widest span per row first, so every row is drawn once
+        // and translucent colors aren't blended twice
+        let mut half_widths = alloc::vec![0; r + 1];
+        for_each_circle_octant(r, |dx, dy| {
+            let (dx, dy) = (dx as usize, dy as usize);
+            half_widths[dy] = half_widths[dy].max(dx);
+            half_widths[dx] = half_widths[dx].max(dy);
+        });
+        // row dy = 0 is part of the middle rectangle
+        for (dy, half_width) in half_widths.iter().enumerate().skip(1) {
+            for row in [top - dy, bottom + dy].iter() {
+                let start = left - half_width;
+                self.draw_rect(start, *row, right + half_width - start + 1, 1, color);
+            }
+        }
+    }
+
+    // draw_rect_outline draws the outline of a rectangle with the given
+    // thickness. The outline is clamped to the screen.
+    pub fn draw_rect_outline(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        color: Color,
+        thickness: usize,
+    ) {
+        let x = x.min(self.width());
+        let y = y.min(self.height());
+        let w = w.min(self.width() - x);
+        let h = h.min(self.height() - y);
+        let thickness = thickness.min(w).min(h);
+        if thickness == 0 {
+            return;
+        }
+        // top and bottom
+        self.draw_rect(x, y, w, thickness, color);
+        self.draw_rect(x, y + h - thickness, w, thickness, color);
+        // left and right span the full height to cover the corners
+        self.draw_rect(x, y, thickness, h, color);
+        self.draw_rect(x + w - thickness, y, thickness, h, color);
+    }
+
+    // draw_progress_bar draws a bar in the (x, y, w, h) rectangle rect whose
+    // left percent are filled with fg and the rest with bg, outlined in a
+    // brighter fg
+    pub fn draw_progress_bar(
+        &mut self,
+        rect: (usize, usize, usize, usize),
+        percent: u8,
+        fg: Color,
+        bg: Color,
+    ) {
+        let (x, y, w, h) = rect;
+        if w == 0 || h == 0 {
+            return;
+        }
+        let filled = percent.min(100) as usize * w / 100;
+        self.draw_rect(x, y, filled, h, fg);
+        self.draw_rect(x + filled, y, w - filled, h, bg);
+        self.draw_rect_border(x, y, w, h, fg.blend(Color::White, 96));
+    }
+
+    // clear_rect fills a rectangle with the background color
+    pub fn clear_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.draw_rect(x, y, w, h, self.background_color);
+    }
+
+    // draw_rect_border is draw_rect_outline with a thickness of one pixel
+    pub fn draw_rect_border(&mut self, x: usize, y: usize, w: usize, h: usize, color: Color) {
+        self.draw_rect_outline(x, y, w, h, color, 1);
+    }
+
+    pub fn draw_rect(
+        &mut self,
+        start_x: usize,
+        start_y: usize,
+        width: usize,
+        height: usize,
+        color: Color,
+    ) {
+        // clip at the screen edges instead of letting write_pixel wrap around
+        let (min_x, min_y, max_x, max_y) = self.clip_bounds();
+        let end_x = start_x.saturating_add(width).min(max_x);
+        let end_y = start_y.saturating_add(height).min(max_y);
+        let (start_x, start_y) = (start_x.max(min_x), start_y.max(min_y));
+        if start_x >= end_x || start_y >= end_y {
+            // fully clipped
+            return;
+        }
+        let (width, height) = (end_x - start_x, end_y - start_y);
+
+        if color.alpha() < 255 {
+            // translucent pixels need the pixel below, blend one by one
+            for y in start_y..start_y + height {
+                for x in start_x..start_x + width {
+                    self.write_pixel(x, y, color);
+                }
+            }
+            return;
+        }
+
+        // fill whole rows at once instead of going through write_pixel
+        let color = self.pixel_bytes(color);
+        let info = self.info.unwrap();
+        let bytes_per_pixel = info.bytes_per_pixel;
+        let framebuffer = target(&mut self.framebuffer, &mut self.draw_buffer);
+        for y in start_y..start_y + height {
+            let row_start = (y * info.stride + start_x) * bytes_per_pixel;
+            let row = &mut framebuffer[row_start..row_start + width * bytes_per_pixel];
+            row.chunks_exact_mut(bytes_per_pixel)
+                .for_each(|p| p.copy_from_slice(&color[..bytes_per_pixel]));
+            let _ = unsafe { ptr::read_volatile(&framebuffer[row_start]) };
+        }
+    }
+}
+
+// fade_level returns the brightness, from 0 to 255, after elapsed of
+// duration ticks of a fade in
+fn fade_level(elapsed: u64, duration: u64) -> u8 {
+    if elapsed >= duration {
+        return 255;
+    }
+    (elapsed * 255 / duration) as u8
+}
+
+// scale_component scales a color component by level / 255
+fn scale_component(c: u8, level: u8) -> u8 {
+    (c as u32 * level as u32 / 255) as u8
+}
+
+// decimal_digits writes the decimal digits of n, most significant first, to
+// buf and returns them
+fn decimal_digits(mut n: usize, buf: &mut [u8; 20]) -> &[u8] {
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buf[start..];
+        }
+    }
+}
+
+// rect_rows returns the byte ranges of the rows of a rectangle in the
+// framebuffer, clipped to the screen
+fn rect_rows(
+    info: &FrameBufferInfo,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = core::ops::Range<usize>> {
+    let bytes_per_pixel = info.bytes_per_pixel;
+    let stride = info.stride;
+    let x_end = (x + width).min(info.horizontal_resolution);
+    let y_end = (y + height).min(info.vertical_resolution);
+    let x = x.min(x_end);
+    (y.min(y_end)..y_end).map(move |row| {
+        (row * stride + x) * bytes_per_pixel..(row * stride + x_end) * bytes_per_pixel
+    })
+}
+
+unsafe impl Send for Display {}
+unsafe impl Sync for Display {}
+
+impl Write for Display {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.write_char_colored(c, self.color, self.background_color);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn test_display(width: usize, height: usize) -> Display {
+        Display::new_for_test(width, height)
+    }
+
+    // pixel returns the color of the pixel at x, y of a test display
+    pub(crate) fn pixel(display: &Display, x: usize, y: usize) -> (u8, u8, u8) {
+        let offset = (y * display.info.unwrap().stride + x) * 4;
+        let fb = display.framebuffer.as_ref().unwrap();
+        (fb[offset], fb[offset + 1], fb[offset + 2])
+    }
+
+    // lit_pixels returns the coordinates of all non black pixels, row by row
+    fn lit_pixels(display: &Display) -> Vec<(usize, usize)> {
+        let info = display.info.unwrap();
+        let mut lit = Vec::new();
+        for y in 0..info.vertical_resolution {
+            for x in 0..info.horizontal_resolution {
+                if pixel(display, x, y) != (0, 0, 0) {
+                    lit.push((x, y));
+                }
+            }
+        }
+        lit
+    }
+
+    #[test]
+    fn test_double_buffering() {
+        let mut display = test_display(16, 16);
+        display.draw_rect(0, 0, 2, 2, Color::Red);
+        assert!(display.enable_double_buffering());
+        assert!(display.draw_buffer.is_some());
+
+        display.draw_rect(4, 4, 2, 2, Color::White);
+        display.write_pixel(8, 8, Color::White);
+        assert_eq!(lit_pixels(&display), [(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(
+            display.snapshot_region(8, 8, 1, 1),
+            alloc::vec![255, 255, 255, 0]
+        );
+
+        display.flip();
+        assert_eq!(lit_pixels(&display).len(), 9);
+        assert_eq!(pixel(&display, 8, 8), (255, 255, 255));
+
+        display.clear();
+        assert_eq!(lit_pixels(&display).len(), 9);
+        display.flip();
+        assert_eq!(lit_pixels(&display), []);
+    }
+
+    #[test]
+    fn test_draw_line() {
+        let mut display = test_display(16, 16);
+        display.draw_line(3, 4, 3, 4, Color::White);
+        assert_eq!(lit_pixels(&display), [(3, 4)]);
+
+        let mut display = test_display(16, 16);
+        display.draw_line(5, 2, 1, 2, Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [(1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]
+        );
+
+        let mut display = test_display(16, 16);
+        display.draw_line(7, 1, 7, 3, Color::White);
+        assert_eq!(lit_pixels(&display), [(7, 1), (7, 2), (7, 3)]);
+
+        let mut display = test_display(16, 16);
+        display.draw_line(0, 0, 3, 3, Color::White);
+        assert_eq!(lit_pixels(&display), [(0, 0), (1, 1), (2, 2), (3, 3)]);
+
+        // shallow slope, one pixel per column
+        let mut display = test_display(16, 16);
+        display.draw_line(0, 0, 6, 2, Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [(0, 0), (1, 0), (2, 1), (3, 1), (4, 1), (5, 2), (6, 2)]
+        );
+
+        // steep slope drawn upwards, one pixel per row
+        let mut display = test_display(16, 16);
+        display.draw_line(1, 4, 0, 0, Color::White);
+        assert_eq!(lit_pixels(&display).len(), 5);
+    }
+
+    #[test]
+    fn test_draw_circle() {
+        let mut display = test_display(32, 32);
+        display.draw_circle(16, 16, 5, Color::White);
+        let white = (255, 255, 255);
+        assert_eq!(pixel(&display, 16, 16), (0, 0, 0));
+        for (x, y) in [(21, 16), (11, 16), (16, 21), (16, 11)].iter() {
+            assert_eq!(pixel(&display, *x, *y), white);
+        }
+        for (x, y) in lit_pixels(&display) {
+            let (dx, dy) = (x as isize - 16, y as isize - 16);
+            assert!(dx * dx + dy * dy <= 6 * 6);
+        }
+
+        let mut display = test_display(32, 32);
+        display.draw_filled_circle(16, 16, 5, Color::White);
+        assert_eq!(pixel(&display, 16, 16), white);
+        assert_eq!(pixel(&display, 19, 18), white);
+        for (x, y) in [(21, 16), (11, 16), (16, 21), (16, 11)].iter() {
+            assert_eq!(pixel(&display, *x, *y), white);
+        }
+        for (x, y) in lit_pixels(&display) {
+            let (dx, dy) = (x as isize - 16, y as isize - 16);
+            assert!(dx * dx + dy * dy <= 6 * 6);
+        }
+
+        // parts left of and above the screen are skipped, not wrapped
+        let mut display = test_display(32, 32);
+        display.draw_filled_circle(1, 1, 3, Color::White);
+        assert_eq!(pixel(&display, 31, 1), (0, 0, 0));
+        assert_eq!(pixel(&display, 1, 31), (0, 0, 0));
+        assert_eq!(pixel(&display, 4, 1), white);
+    }
+
+    #[test]
+    fn test_draw_ellipse() {
+        let white = (255, 255, 255);
+        // inside returns whether (x, y) is within the ellipse around (16, 16)
+        // with the radii grown by one pixel
+        let inside = |x: usize, y: usize, rx: isize, ry: isize| {
+            let (dx, dy) = (x as isize - 16, y as isize - 16);
+            let (rx, ry) = (rx + 1, ry + 1);
+            dx * dx * ry * ry + dy * dy * rx * rx <= rx * rx * ry * ry
+        };
+
+        let mut display = test_display(32, 32);
+        display.draw_ellipse(16, 16, 10, 4, Color::White);
+        assert_eq!(pixel(&display, 16, 16), (0, 0, 0));
+        for (x, y) in [(26, 16), (6, 16), (16, 20), (16, 12)].iter() {
+            assert_eq!(pixel(&display, *x, *y), white);
+        }
+        for (x, y) in lit_pixels(&display) {
+            assert!(inside(x, y, 10, 4));
+        }
+        // the outline has no gaps in the columns it spans
+        for x in 6..=26 {
+            assert!((0..32).any(|y| pixel(&display, x, y) == white));
+        }
+
+        let mut display = test_display(32, 32);
+        display.draw_filled_ellipse(16, 16, 4, 10, Color::White);
+        for (x, y) in [(16, 16), (20, 16), (12, 16), (16, 26), (16, 6)].iter() {
+            assert_eq!(pixel(&display, *x, *y), white);
+        }
+        for (x, y) in lit_pixels(&display) {
+            assert!(inside(x, y, 4, 10));
+        }
+
+        // flat ellipses degenerate into lines
+        let mut display = test_display(32, 32);
+        display.draw_ellipse(16, 16, 3, 0, Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [
+                (13, 16),
+                (14, 16),
+                (15, 16),
+                (16, 16),
+                (17, 16),
+                (18, 16),
+                (19, 16)
+            ]
+        );
+        let mut display = test_display(32, 32);
+        display.draw_filled_ellipse(16, 16, 0, 2, Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [(16, 14), (16, 15), (16, 16), (16, 17), (16, 18)]
+        );
+
+        // parts outside the screen are clipped, not wrapped
+        let mut display = test_display(32, 32);
+        display.draw_filled_ellipse(1, 30, 6, 4, Color::White);
+        assert_eq!(pixel(&display, 7, 30), white);
+        assert_eq!(pixel(&display, 31, 30), (0, 0, 0));
+        assert_eq!(pixel(&display, 1, 0), (0, 0, 0));
+        display.draw_ellipse(30, 1, 6, 4, Color::White);
+        assert_eq!(pixel(&display, 24, 1), white);
+        assert_eq!(pixel(&display, 0, 1), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_triangle() {
+        // right angle
+        let mut display = test_display(16, 16);
+        display.draw_triangle((0, 0), (0, 4), (4, 4), Color::White);
+        let lit = lit_pixels(&display);
+        assert_eq!(lit.len(), 1 + 2 + 3 + 4 + 5);
+        assert!(lit.iter().all(|(x, y)| x <= y && *y <= 4));
+
+        // a single pixel
+        let mut display = test_display(16, 16);
+        display.draw_triangle((7, 3), (7, 3), (7, 3), Color::White);
+        assert_eq!(lit_pixels(&display), [(7, 3)]);
+
+        // collinear points draw the line between them
+        let mut display = test_display(16, 16);
+        display.draw_triangle((4, 4), (0, 0), (2, 2), Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]
+        );
+        let mut display = test_display(16, 16);
+        display.draw_triangle((1, 5), (6, 5), (3, 5), Color::White);
+        assert_eq!(lit_pixels(&display).len(), 6);
+
+        // clipped at the bottom and right edge
+        let mut display = test_display(16, 16);
+        display.draw_triangle((10, 10), (30, 10), (10, 30), Color::White);
+        assert_eq!(pixel(&display, 15, 10), (255, 255, 255));
+        assert_eq!(pixel(&display, 0, 10), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_rounded_rect() {
+        let mut display = test_display(32, 32);
+        display.draw_rounded_rect(2, 4, 20, 10, 3, Color::White);
+        let white = (255, 255, 255);
+        // the corners are cut off, the edge centers are not
+        for (x, y) in [(2, 4), (21, 4), (2, 13), (21, 13)].iter() {
+            assert_eq!(pixel(&display, *x, *y), (0, 0, 0));
+        }
+        for (x, y) in [(12, 4), (12, 13), (2, 8), (21, 8), (5, 4), (3, 5)].iter() {
+            assert_eq!(pixel(&display, *x, *y), white);
+        }
+        for (x, y) in lit_pixels(&display) {
+            assert!((2..22).contains(&x) && (4..14).contains(&y));
+        }
+
+        // the radius is clamped, a square becomes a circle
+        let mut display = test_display(32, 32);
+        display.draw_rounded_rect(0, 0, 11, 11, 100, Color::White);
+        let mut circle = test_display(32, 32);
+        circle.draw_filled_circle(5, 5, 5, Color::White);
+        assert_eq!(lit_pixels(&display), lit_pixels(&circle));
+
+        // translucent colors are blended once, the corners too
+        let mut display = test_display(32, 32);
+        display.draw_rounded_rect(2, 4, 20, 10, 3, Color::RGBA32(0x80ffffff));
+        let shade = pixel(&display, 12, 8);
+        assert_ne!(shade, (0, 0, 0));
+        for (x, y) in lit_pixels(&display) {
+            assert_eq!(pixel(&display, x, y), shade);
+        }
+    }
+
+    #[test]
+    fn test_draw_rect_outline() {
+        let mut display = test_display(64, 48);
+        display.draw_rect_outline(10, 10, 20, 10, Color::Red, 2);
+
+        let red = (255, 0, 0);
+        assert_eq!(pixel(&display, 10, 10), red);
+        assert_eq!(pixel(&display, 29, 10), red);
+        assert_eq!(pixel(&display, 10, 19), red);
+        assert_eq!(pixel(&display, 29, 19), red);
+        assert_eq!(pixel(&display, 11, 11), red);
+        assert_eq!(pixel(&display, 12, 12), (0, 0, 0));
+        assert_eq!(pixel(&display, 9, 9), (0, 0, 0));
+        assert_eq!(pixel(&display, 30, 20), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut display = test_display(16, 16);
+        let mut buf = display.create_buffer(4, 4);
+        assert_eq!((buf.width(), buf.height()), (4, 4));
+        buf.write_pixel(1, 1, Color::Red);
+        buf.write_pixel(2, 1, Color::Blue);
+        buf.write_pixel(3, 3, Color::White);
+
+        display.blit(&buf, (1, 1), (10, 5), (2, 2));
+        assert_eq!(lit_pixels(&display), [(10, 5), (11, 5)]);
+        assert_eq!(pixel(&display, 10, 5), (255, 0, 0));
+        assert_eq!(pixel(&display, 11, 5), (0, 0, 255));
+
+        // clipped to the buffer and the screen
+        let mut display = test_display(16, 16);
+        display.blit(&buf, (0, 0), (14, 14), (10, 10));
+        assert_eq!(lit_pixels(&display), [(15, 15)]);
+    }
+
+    #[test]
+    fn test_font_scale() {
+        let mut display = test_display(64, 64);
+        display.set_font_scale(2);
+        assert_eq!(display.measure_text("ab"), (32, 16 + LINE_SPACING));
+        display.draw_text_at(0, 0, &['|', '|'], Color::White, Color::Black);
+        assert_eq!(display.x_pos, 32);
+        let lit = lit_pixels(&display);
+        assert!(!lit.is_empty());
+        // each font pixel is a 2x2 square
+        for (x, y) in lit.iter() {
+            let (x0, y0) = (x - x % 2, y - y % 2);
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                assert_eq!(pixel(&display, x0 + dx, y0 + dy), (255, 255, 255));
+            }
+            assert!(*y < 16);
+        }
+        // and the second glyph is the first one moved by 16 pixels
+        let first: Vec<_> = lit.iter().filter(|(x, _)| *x < 16).collect();
+        let second: Vec<_> = lit.iter().filter(|(x, _)| *x >= 16).collect();
+        assert_eq!(first.len(), second.len());
+
+        display.set_font_scale(1);
+        assert_eq!(display.measure_text("ab"), (16, 8 + LINE_SPACING));
+    }
+
+    #[test]
+    fn test_bold() {
+        for scale in [1, 2].iter() {
+            let mut plain = test_display(64, 64);
+            plain.set_font_scale(*scale);
+            plain.draw_text_at(0, 0, &['1'], Color::White, Color::Black);
+            let mut bold = test_display(64, 64);
+            bold.set_font_scale(*scale);
+            bold.set_bold(true);
+            bold.draw_text_at(0, 0, &['1'], Color::White, Color::Black);
+            assert_eq!(bold.x_pos, 9 * scale);
+
+            // bold is the glyph merged with itself moved one font pixel right
+            let plain = lit_pixels(&plain);
+            let bold = lit_pixels(&bold);
+            for (x, y) in plain.iter() {
+                assert!(bold.contains(&(*x, *y)));
+                assert!(bold.contains(&(x + scale, *y)));
+            }
+            for (x, y) in bold.iter() {
+                assert!(plain.contains(&(*x, *y)) || plain.contains(&(x - scale, *y)));
+            }
+        }
+
+        let mut display = test_display(64, 64);
+        display.set_bold(true);
+        assert_eq!(display.measure_text("ab"), (18, 8 + LINE_SPACING));
+        display.draw_number_large(0, 0, 11, Color::White);
+        assert!(lit_pixels(&display).iter().all(|(x, _)| *x < 19));
+        display.set_bold(false);
+        assert_eq!(display.measure_text("ab"), (16, 8 + LINE_SPACING));
+    }
+
+    #[test]
+    fn test_underline() {
+        let mut plain = test_display(32, 32);
+        plain.draw_text_at(0, 0, &['a'], Color::White, Color::Black);
+        let mut display = test_display(32, 32);
+        display.set_underline(true);
+        display.draw_text_at(0, 0, &['a'], Color::White, Color::Black);
+        let lit = lit_pixels(&display);
+        // the glyph rows are unaffected
+        let glyph: Vec<_> = lit.iter().filter(|(_, y)| *y < 8).cloned().collect();
+        assert_eq!(glyph, lit_pixels(&plain));
+        // and the row below is set for the width of the glyph
+        let underline: Vec<_> = lit.iter().filter(|(_, y)| *y >= 8).cloned().collect();
+        assert_eq!(underline, (0..8).map(|x| (x, 8)).collect::<Vec<_>>());
+
+        // with bold and scaling, the line moves below the scaled glyph and
+        // spans the bold width
+        let mut display = test_display(32, 32);
+        display.set_underline(true);
+        display.set_bold(true);
+        display.set_font_scale(2);
+        display.draw_text_at(0, 0, &['a'], Color::White, Color::Black);
+        let underline: Vec<_> = lit_pixels(&display)
+            .into_iter()
+            .filter(|(_, y)| *y >= 16)
+            .collect();
+        assert_eq!(underline, (0..18).map(|x| (x, 16)).collect::<Vec<_>>());
+
+        // turning it off again leaves the row below the next glyph alone
+        display.set_underline(false);
+        display.set_bold(false);
+        display.set_font_scale(1);
+        display.draw_text_at(0, 20, &['a'], Color::White, Color::Black);
+        assert!(lit_pixels(&display).iter().all(|(_, y)| *y != 28));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_font_scale_unsupported() {
+        test_display(8, 8).set_font_scale(3);
+    }
+
+    #[test]
+    fn test_clip_rect() {
+        let mut display = test_display(16, 16);
+        display.set_clip_rect(4, 4, 4, 2);
+        display.draw_rect(0, 0, 16, 16, Color::White);
+        display.write_pixel(2, 2, Color::White);
+        display.write_pixel(20, 5, Color::White);
+        let lit = lit_pixels(&display);
+        assert_eq!(lit.len(), 8);
+        assert!(lit
+            .iter()
+            .all(|(x, y)| (4..8).contains(x) && (4..6).contains(y)));
+
+        let mut buf = display.create_buffer(4, 4);
+        buf.write_pixel(0, 0, Color::Red);
+        buf.write_pixel(3, 3, Color::Red);
+        display.set_clip_rect(10, 10, 2, 2);
+        display.blit(&buf, (0, 0), (9, 9), (4, 4));
+        assert_eq!(pixel(&display, 9, 9), (0, 0, 0));
+        assert_eq!(pixel(&display, 12, 12), (0, 0, 0));
+        assert_eq!(pixel(&display, 11, 11), (0, 0, 0));
+        display.blit(&buf, (0, 0), (11, 11), (4, 4));
+        assert_eq!(pixel(&display, 11, 11), (255, 0, 0));
+
+        // clear_clip gives back the whole screen
+        display.clear_clip();
+        display.draw_rect(0, 0, 16, 16, Color::White);
+        assert_eq!(lit_pixels(&display).len(), 16 * 16);
+    }
+
+    #[test]
+    fn test_blit_bmp() {
+        let (red, blue) = ((255, 0, 0), (0, 0, 255));
+        let img = bmp::decode(bmp::tests::encode(&[&[red, red], &[blue, blue]])).unwrap();
+        let mut display = test_display(16, 16);
+        display.blit_bmp(&img, 3, 4);
+        // the top row of the image is the last one in the file
+        assert_eq!(lit_pixels(&display), [(3, 4), (4, 4), (3, 5), (4, 5)]);
+        assert_eq!(pixel(&display, 4, 4), red);
+        assert_eq!(pixel(&display, 3, 5), blue);
+
+        // clipped at the screen edge instead of wrapping
+        let mut display = test_display(16, 16);
+        display.blit_bmp(&img, 15, 15);
+        assert_eq!(lit_pixels(&display), [(15, 15)]);
+    }
+
+    #[test]
+    fn test_draw_bitmap() {
+        let mut display = test_display(16, 16);
+        // 10 pixels wide, so every row takes two bytes
+        let bitmap = [0b1000_0000, 0b0100_0000, 0b0000_0001, 0b1000_0000];
+        display.draw_bitmap((2, 3, 10, 2), &bitmap, Color::White, Color::Red);
+        assert_eq!(lit_pixels(&display).len(), 20);
+        let white: Vec<_> = lit_pixels(&display)
+            .into_iter()
+            .filter(|(x, y)| pixel(&display, *x, *y) == (255, 255, 255))
+            .collect();
+        assert_eq!(white, [(2, 3), (11, 3), (9, 4), (10, 4)]);
+        assert_eq!(pixel(&display, 3, 3), (255, 0, 0));
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let line = 8 + LINE_SPACING;
+        let mut display = test_display(16, 4 * line);
+        display.draw_rect(0, line, 16, 1, Color::White);
+        display.draw_rect(0, 3 * line, 16, 1, Color::Red);
+        display.set_xy(0, 3 * line);
+
+        display.scroll_up(line);
+        assert_eq!(pixel(&display, 5, 0), (255, 255, 255));
+        assert_eq!(pixel(&display, 5, line), (0, 0, 0));
+        assert_eq!(pixel(&display, 5, 2 * line), (255, 0, 0));
+        assert_eq!(pixel(&display, 5, 3 * line), (0, 0, 0));
+        assert_eq!(display.y_pos, 2 * line);
+
+        // writing past the last line scrolls instead of clearing
+        display.set_xy(0, 4 * line - 8);
+        display.write_char_colored('x', Color::White, Color::Black);
+        assert!(display.y_pos < 4 * line - 8);
+        assert_eq!(pixel(&display, 5, line), (255, 0, 0));
+
+        // smooth scrolling by single pixels
+        display.scroll_up(1);
+        assert_eq!(pixel(&display, 5, line - 1), (255, 0, 0));
+        assert_eq!(pixel(&display, 5, line), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_progress_bar() {
+        let mut display = test_display(32, 8);
+        display.draw_progress_bar((0, 0, 20, 4), 50, Color::Red, Color::Blue);
+        assert_eq!(pixel(&display, 5, 2), (255, 0, 0));
+        assert_eq!(pixel(&display, 9, 2), (255, 0, 0));
+        assert_eq!(pixel(&display, 10, 2), (0, 0, 255));
+        assert_eq!(pixel(&display, 18, 2), (0, 0, 255));
+        assert_eq!(pixel(&display, 0, 0), (255, 96, 96));
+        assert_eq!(pixel(&display, 19, 3), (255, 96, 96));
+        assert_eq!(pixel(&display, 20, 2), (0, 0, 0));
+
+        display.draw_progress_bar((0, 0, 20, 4), 250, Color::Red, Color::Blue);
+        assert_eq!(pixel(&display, 18, 2), (255, 0, 0));
+
+        let mut display = test_display(32, 8);
+        display.draw_progress_bar((3, 3, 0, 4), 50, Color::Red, Color::Blue);
+        assert_eq!(lit_pixels(&display), []);
+    }
+
+    #[test]
+    fn test_clear_rect() {
+        let mut display = test_display(16, 16);
+        display.draw_rect(0, 0, 16, 16, Color::White);
+        display.background_color = Color::Blue;
+        display.clear_rect(4, 5, 3, 2);
+
+        for y in 0..16 {
+            for x in 0..16 {
+                let inside = (4..7).contains(&x) && (5..7).contains(&y);
+                let want = if inside { (0, 0, 255) } else { (255, 255, 255) };
+                assert_eq!(pixel(&display, x, y), want);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_rect_border() {
+        let mut display = test_display(16, 16);
+        display.draw_rect_border(2, 3, 4, 3, Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [
+                (2, 3),
+                (3, 3),
+                (4, 3),
+                (5, 3),
+                (2, 4),
+                (5, 4),
+                (2, 5),
+                (3, 5),
+                (4, 5),
+                (5, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw_rect() {
+        let mut display = test_display(64, 48);
+        display.draw_rect(5, 6, 10, 4, Color::RGB32(0x123456));
+
+        let color = (0x12, 0x34, 0x56);
+        assert_eq!(pixel(&display, 5, 6), color);
+        assert_eq!(pixel(&display, 14, 9), color);
+        assert_eq!(pixel(&display, 10, 7), color);
+        assert_eq!(pixel(&display, 4, 6), (0, 0, 0));
+        assert_eq!(pixel(&display, 15, 6), (0, 0, 0));
+        assert_eq!(pixel(&display, 5, 5), (0, 0, 0));
+        assert_eq!(pixel(&display, 5, 10), (0, 0, 0));
+
+        // partially off screen rects are clipped at the edges
+        display.draw_rect(62, 0, 4, 1, Color::White);
+        assert_eq!(pixel(&display, 63, 0), (255, 255, 255));
+        assert_eq!(pixel(&display, 0, 0), (0, 0, 0));
+        assert_eq!(pixel(&display, 1, 0), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_rect_off_screen() {
+        let mut display = test_display(64, 48);
+        display.draw_rect(63, 47, 10, 10, Color::White);
+        display.draw_rect(64, 0, 10, 10, Color::White);
+        display.draw_rect(0, 48, 10, 10, Color::White);
+
+        let lit = (0..48)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(&display, x, y) != (0, 0, 0))
+            .count();
+        assert_eq!(lit, 1);
+        assert_eq!(pixel(&display, 63, 47), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_write_block_row() {
+        let mut display = test_display(64, 48);
+        display.write_block_row(8, &[40, 0, 16], Color::Blue);
+
+        for x in [0, 16, 40].iter() {
+            assert_eq!(pixel(&display, *x, 8), (0, 0, 255));
+            assert_eq!(
+                pixel(&display, x + BLOCK_SIZE - 1, 8 + BLOCK_SIZE - 1),
+                (0, 0, 255)
+            );
+        }
+        assert_eq!(pixel(&display, 8, 8), (0, 0, 0));
+        assert_eq!(pixel(&display, 24, 12), (0, 0, 0));
+        assert_eq!(pixel(&display, 0, 7), (0, 0, 0));
+        assert_eq!(pixel(&display, 0, 16), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_gradient_rect() {
+        let end = Color::RGB((200, 100, 0));
+        let mut display = test_display(16, 16);
+        display.draw_gradient_rect((2, 3, 11, 4), Color::Black, end, true);
+        assert_eq!(pixel(&display, 2, 3), (0, 0, 0));
+        // the middle column is at 127 / 255
+        assert_eq!(pixel(&display, 7, 6), (99, 49, 0));
+        assert_eq!(pixel(&display, 12, 3), (200, 100, 0));
+        assert_eq!(pixel(&display, 12, 6), (200, 100, 0));
+        assert_eq!(pixel(&display, 13, 3), (0, 0, 0));
+        assert_eq!(pixel(&display, 12, 7), (0, 0, 0));
+
+        let mut display = test_display(16, 16);
+        display.draw_gradient_rect((2, 3, 4, 11), Color::Black, end, false);
+        assert_eq!(pixel(&display, 5, 3), (0, 0, 0));
+        assert_eq!(pixel(&display, 2, 8), (99, 49, 0));
+        assert_eq!(pixel(&display, 5, 13), (200, 100, 0));
+        assert_eq!(pixel(&display, 6, 13), (0, 0, 0));
+
+        // a single row gets the start color
+        let mut display = test_display(16, 16);
+        display.draw_gradient_rect((0, 0, 4, 1), Color::White, Color::Black, false);
+        assert_eq!(pixel(&display, 3, 0), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_draw_gradient_rect_v() {
+        let mut display = test_display(64, 48);
+        display.draw_gradient_rect_v(10, 0, 4, 11, Color::Black, Color::RGB((200, 100, 0)));
+
+        assert_eq!(pixel(&display, 10, 0), (0, 0, 0));
+        assert_eq!(pixel(&display, 13, 10), (200, 100, 0));
+        let (r, g, b) = pixel(&display, 12, 5);
+        assert!((99..=101).contains(&r));
+        assert!((49..=51).contains(&g));
+        assert_eq!(b, 0);
+        assert_eq!(pixel(&display, 14, 5), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_glyph_width() {
+        let mut display = test_display(64, 48);
+        assert_eq!(display.glyph_width('a'), 8);
+        assert_eq!(display.measure_text("2048: ok\n").0, 64);
+        assert_eq!(display.measure_text("").0, 0);
+
+        display.set_xy(0, 0);
+        for c in "ab".chars() {
+            display.write_char_colored(c, Color::White, Color::Black);
+        }
+        assert_eq!(display.x_pos, display.measure_text("ab").0);
+
+        // basic and extra glyphs mixed
+        assert_eq!(display.glyph_width('↑'), 4);
+        assert_eq!(display.str_pixel_width("a↑b·"), 8 + 4 + 8 + 4);
+        assert_eq!(display.measure_text("↑↓\n2048").0, 32);
+        display.set_xy(0, 0);
+        for c in "a↑b".chars() {
+            display.write_char_colored(c, Color::White, Color::Black);
+        }
+        assert_eq!(display.x_pos, 20);
+        assert_eq!(pixel(&display, 9, 1), (255, 255, 255));
+        assert_eq!(pixel(&display, 11, 1), (0, 0, 0));
+
+        // tiny digits
+        display.set_tiny_font(true);
+        assert_eq!(display.glyph_width('7'), 5);
+        assert_eq!(display.glyph_width('a'), 8);
+        assert_eq!(display.str_pixel_width("a10"), 18);
+        display.set_bold(true);
+        assert_eq!(display.glyph_width('7'), 6);
+    }
+
+    #[test]
+    fn test_measure_text() {
+        let mut display = test_display(64, 48);
+        assert_eq!(display.measure_text("snake"), (40, 8 + LINE_SPACING));
+        assert_eq!(display.measure_text("größe"), (40, 8 + LINE_SPACING));
+        assert_eq!(
+            display.measure_text("ab\nabcd\n"),
+            (32, 3 * (8 + LINE_SPACING))
+        );
+        assert_eq!(display.measure_chars(&['1', '2']), (16, 8 + LINE_SPACING));
+
+        // characters missing in the font are drawn as '?' instead of panicking
+        display.write_char_colored('\u{2603}', Color::White, Color::Black);
+        assert_eq!(display.x_pos, 8);
+        assert_ne!(lit_pixels(&display), []);
+    }
+
+    #[test]
+    fn test_draw_char_matrix() {
+        let mut display = test_display(64, 64);
+        display.draw_char_matrix(8, 4, &["A", "A"], Color::White, Color::Black);
+
+        // the second line is the same glyph, LINE_SPACING pixels below the first
+        let mut lit = 0;
+        for dy in 0..8 {
+            for dx in 0..8 {
+                let first = pixel(&display, 8 + dx, 4 + dy);
+                assert_eq!(first, pixel(&display, 8 + dx, 14 + dy));
+                if first == (255, 255, 255) {
+                    lit += 1;
+                }
+            }
+        }
+        assert!(lit > 0);
+        for dx in 0..8 {
+            assert_eq!(pixel(&display, 8 + dx, 12), (0, 0, 0));
+            assert_eq!(pixel(&display, 8 + dx, 13), (0, 0, 0));
+        }
+    }
+
+    #[test]
+    fn test_write_block_colored_border() {
+        let mut display = test_display(32, 32);
+        display.write_block_colored_border(8, 8, Color::Green, Color::White);
+
+        let last = 8 + BLOCK_SIZE - 1;
+        for (x, y) in [(8, 8), (last, 8), (8, last), (last, last)] {
+            assert_eq!(pixel(&display, x, y), (255, 255, 255));
+        }
+        assert_eq!(pixel(&display, 12, 12), (0, 255, 0));
+        assert_eq!(pixel(&display, 9, 9), (0, 255, 0));
+        assert_eq!(pixel(&display, last + 1, last + 1), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_copy_rect_back_buffer() {
+        let mut display = test_display(32, 32);
+        assert!(display.enable_back_buffer());
+
+        display.draw_rect(4, 4, 8, 8, Color::Red);
+        display.draw_rect(6, 6, 2, 2, Color::Blue);
+        display.copy_rect_to_back(4, 4, 8, 8);
+
+        display.draw_rect(0, 0, 32, 32, Color::Black);
+        display.copy_rect_from_back(4, 4, 8, 8);
+        assert_eq!(pixel(&display, 4, 4), (255, 0, 0));
+        assert_eq!(pixel(&display, 6, 7), (0, 0, 255));
+        assert_eq!(pixel(&display, 11, 11), (255, 0, 0));
+        assert_eq!(pixel(&display, 12, 12), (0, 0, 0));
+        assert_eq!(pixel(&display, 3, 4), (0, 0, 0));
+
+        // rectangles are clipped at the screen border
+        display.copy_rect_to_back(28, 28, 8, 8);
+        display.copy_rect_from_back(28, 28, 8, 8);
+    }
+
+    #[test]
+    fn test_draw_borders_with_color() {
+        let mut display = test_display(64, 48);
+        display.draw_borders_with_color(Color::DarkBlue);
+        assert_eq!(
+            pixel(&display, BLOCK_SIZE, BLOCK_SIZE),
+            Color::DarkBlue.rgb()
+        );
+        assert_eq!(
+            pixel(&display, 63 - BLOCK_SIZE, 47 - BLOCK_SIZE),
+            Color::DarkBlue.rgb()
+        );
+        assert_eq!(pixel(&display, 32, 24), (0, 0, 0));
+
+        display.draw_borders();
+        assert_eq!(pixel(&display, BLOCK_SIZE, 24), Color::DarkGreen.rgb());
+    }
+
+    #[test]
+    fn test_draw_lines() {
+        let mut display = test_display(16, 16);
+        display.draw_horizontal_line(2, 3, 6, Color::White);
+        display.draw_vertical_line(10, 4, 6, Color::White);
+        display.draw_horizontal_line(8, 6, 6, Color::White);
+        display.draw_vertical_line(12, 9, 3, Color::White);
+        assert_eq!(
+            lit_pixels(&display),
+            [(3, 2), (4, 2), (5, 2), (10, 4), (10, 5)]
+        );
+    }
+
+    #[test]
+    fn test_decimal_digits() {
+        let mut buf = [0; 20];
+        assert_eq!(decimal_digits(0, &mut buf), [0]);
+        assert_eq!(decimal_digits(7, &mut buf), [7]);
+        assert_eq!(decimal_digits(1024, &mut buf), [1, 0, 2, 4]);
+        assert_eq!(decimal_digits(usize::MAX, &mut buf).len(), 20);
+    }
+
+    #[test]
+    fn test_draw_number_large() {
+        let mut display = test_display(32, 32);
+        display.draw_number_large(0, 0, 10, Color::White);
+
+        // the top row of "1" has bits 2 and 3 set, doubled in the second row
+        assert_eq!(pixel(&display, 2, 0), (255, 255, 255));
+        assert_eq!(pixel(&display, 2, 1), (255, 255, 255));
+        assert_eq!(pixel(&display, 1, 0), (0, 0, 0));
+        // "0" starts 9 pixels to the right, its top row has bits 1 to 5 set
+        assert_eq!(pixel(&display, 9 + 1, 0), (255, 255, 255));
+        assert_eq!(pixel(&display, 9, 0), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_fade_level() {
+        assert_eq!(fade_level(0, 18), 0);
+        assert_eq!(fade_level(9, 18), 127);
+        assert_eq!(fade_level(18, 18), 255);
+        assert_eq!(fade_level(30, 18), 255);
+        assert_eq!(fade_level(0, 0), 255);
+
+        assert_eq!(scale_component(200, 0), 0);
+        assert_eq!(scale_component(200, 255), 200);
+        assert_eq!(scale_component(200, 127), 99);
+    }
+
+    #[test]
+    fn test_animate_fade_in() {
+        let mut display = test_display(8, 8);
+        // without double buffering there is nothing to fade from
+        display.draw_rect(0, 0, 8, 8, Color::White);
+        display.animate_fade_in(10, 10, 4);
+        assert_eq!(pixel(&display, 3, 3), (255, 255, 255));
+
+        assert!(display.enable_double_buffering());
+        display.animate_fade_in(10, 10, 4);
+        display.flip();
+        assert_eq!(pixel(&display, 3, 3), (0, 0, 0));
+        display.animate_fade_in(12, 10, 4);
+        display.flip();
+        assert_eq!(pixel(&display, 3, 3), (127, 127, 127));
+
+        // drawing during the fade is faded, not lost
+        display.draw_rect(0, 0, 4, 4, Color::Red);
+        display.animate_fade_in(13, 10, 4);
+        display.flip();
+        assert_eq!(pixel(&display, 3, 3), (191, 0, 0));
+        display.animate_fade_in(14, 10, 4);
+        display.flip();
+        assert_eq!(pixel(&display, 3, 3), (255, 0, 0));
+        assert_eq!(pixel(&display, 5, 5), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_snapshot_region() {
+        let mut display = test_display(32, 32);
+        display.draw_rect(4, 4, 8, 8, Color::Red);
+        display.draw_rect(6, 6, 2, 2, Color::Blue);
+
+        let snapshot = display.snapshot_region(4, 4, 8, 8);
+        assert_eq!(snapshot.len(), 8 * 8 * 4);
+        display.draw_rect(0, 0, 32, 32, Color::Black);
+        display.restore_region(4, 4, 8, 8, &snapshot);
+
+        assert_eq!(pixel(&display, 4, 4), (255, 0, 0));
+        assert_eq!(pixel(&display, 7, 7), (0, 0, 255));
+        assert_eq!(pixel(&display, 11, 11), (255, 0, 0));
+        assert_eq!(pixel(&display, 12, 4), (0, 0, 0));
+
+        // clipped at the screen border
+        let snapshot = display.snapshot_region(30, 30, 8, 8);
+        assert_eq!(snapshot.len(), 2 * 2 * 4);
+        display.restore_region(30, 30, 8, 8, &snapshot);
+    }
+
+    #[test]
+    fn test_pixel_formats() {
+        let color = Color::RGB32(0x204080);
+        let mut display = test_display(4, 4);
+        assert_eq!(display.validate_pixel_format(), Ok(()));
+        assert_eq!(display.pixel_bytes(color), [0x20, 0x40, 0x80, 0]);
+
+        let mut info = display.info.unwrap();
+        info.pixel_format = PixelFormat::BGR;
+        display.info = Some(info);
+        assert_eq!(display.validate_pixel_format(), Ok(()));
+        assert_eq!(display.pixel_bytes(color), [0x80, 0x40, 0x20, 0]);
+
+        info.pixel_format = PixelFormat::U8;
+        display.info = Some(info);
+        assert_eq!(display.validate_pixel_format(), Ok(()));
+        assert_eq!(display.pixel_bytes(color), [0x3d, 0, 0, 0]);
+        assert_eq!(display.pixel_bytes(Color::White), [255, 0, 0, 0]);
+
+        info.pixel_format = PixelFormat::RGB;
+        info.bytes_per_pixel = 2;
+        display.info = Some(info);
+        assert_eq!(
+            display.validate_pixel_format(),
+            Err(UnsupportedPixelFormat {
+                pixel_format: PixelFormat::RGB,
+                bytes_per_pixel: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_pixel_rgb32() {
+        let mut display = test_display(4, 4);
+        display.write_pixel(1, 2, Color::RGB32(0xeee4da));
+        assert_eq!(pixel(&display, 1, 2), (0xee, 0xe4, 0xda));
+        assert_eq!(Color::RGB32(0xeee4da).rgb(), (0xee, 0xe4, 0xda));
+    }
+
+    #[test]
+    fn test_alpha_blending() {
+        let mut display = test_display(8, 8);
+        display.draw_rect(0, 0, 8, 8, Color::RGB32(0x0000ff));
+        display.write_pixel(1, 1, Color::RGBA32(0x80ff0000));
+        assert_eq!(pixel(&display, 1, 1), (128, 0, 127));
+
+        display.draw_rect(2, 2, 2, 2, Color::RGBA32(0x00ffffff));
+        assert_eq!(pixel(&display, 2, 2), (0, 0, 255));
+        display.draw_rect(2, 2, 2, 2, Color::RGBA32(0xffffffff));
+        assert_eq!(pixel(&display, 3, 3), (255, 255, 255));
+
+        let mut info = display.info.unwrap();
+        info.pixel_format = PixelFormat::BGR;
+        display.info = Some(info);
+        display.write_pixel(1, 1, Color::RGBA32(0x80ff0000));
+        // the bytes are read back as BGR, blended with red and written as BGR
+        assert_eq!(pixel(&display, 1, 1), (63, 0, 191));
+
+        info.pixel_format = PixelFormat::U8;
+        display.info = Some(info);
+        display.write_pixel(5, 5, Color::RGBA32(0x10ffffff));
+        let fb = display.framebuffer.as_ref().unwrap();
+        assert_eq!(fb[(5 * 8 + 5) * 4], 255);
+    }
+
+    #[test]
+    fn test_lerp() {
+        assert_eq!(
+            Color::lerp(Color::Green, Color::DarkGreen, 0),
+            Color::RGB32(0x00ff00)
+        );
+        assert_eq!(
+            Color::lerp(Color::Green, Color::DarkGreen, 255),
+            Color::RGB32(0x004000)
+        );
+        assert_eq!(
+            Color::lerp(Color::Black, Color::Magenta, 128),
+            Color::RGB32(0x800080)
+        );
+    }
+
+    #[test]
+    fn test_is_dark() {
+        assert!(!Color::White.is_dark());
+        assert!(Color::Black.is_dark());
+        assert!(!Color::Yellow.is_dark());
+        assert!(Color::Blue.is_dark());
+        assert!(!Color::RGB32(0xeee4da).is_dark());
+    }
+
+    #[test]
+    fn test_frame_due() {
+        let mut display = Display::new();
+        for _ in 0..5 {
+            display.tick();
+            assert!(display.frame_due());
+        }
+
+        display.set_fps_target(6);
+        let drawn: alloc::vec::Vec<bool> = (0..9)
+            .map(|_| {
+                display.tick();
+                display.frame_due()
+            })
+            .collect();
+        assert_eq!(
+            drawn,
+            [false, false, true, false, false, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_boot_logo_size() {
+        assert_eq!(BOOT_LOGO.len(), 16);
+        for row in BOOT_LOGO.iter() {
+            assert_eq!(row.len(), 8);
+        }
+    }
+
+    #[test]
+    fn test_print_boot_logo() {
+        let mut display = test_display(320, 80);
+        display.draw_rect(0, 0, 320, 80, Color::White);
+        display.set_xy(0, 0);
+        display.print_boot_logo();
+
+        // the logo starts at x 32, the second row of its first byte has bits 2
+        // to 6 set
+        assert_eq!(
+            pixel(&display, 32 + 2 * LOGO_SCALE, LOGO_SCALE),
+            Color::Green.rgb()
+        );
+        assert_eq!(pixel(&display, 32 + LOGO_SCALE, LOGO_SCALE), (0, 0, 0));
+        assert_eq!(pixel(&display, 31, LOGO_SCALE), (255, 255, 255));
+        assert_eq!(pixel(&display, 32 + 64 * LOGO_SCALE, 0), (255, 255, 255));
+        assert_eq!(display.y_pos, 16 * LOGO_SCALE + LINE_SPACING);
+    }
+}
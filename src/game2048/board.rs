@@ -1,16 +1,21 @@
 use crate::world::Direction;
 use alloc::vec::Vec;
+use core::convert::TryInto;
 use rand::prelude::*;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct World {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Tile {
     pub val: Option<u64>,
     pub changed: bool,
     pub row: usize,
     pub col: usize,
+    // number of merges that produced this tile
+    pub merge_count: u32,
+    // true if the tile was merged in the last move
+    pub merged: bool,
 }
 
 impl Tile {
@@ -20,6 +25,8 @@ impl Tile {
             changed: false,
             row,
             col,
+            merge_count: 0,
+            merged: false,
         }
     }
 
@@ -33,15 +40,28 @@ impl Tile {
     }
 }
 
+// cloning a board copies the rng as well, so the clone spawns the same tiles
+#[derive(Clone)]
 pub(crate) struct Board {
     rows: usize,
     cols: usize,
     tiles: Vec<Tile>,
     rng: rand::rngs::SmallRng,
+    // number of move_direction calls
+    move_count: u64,
+    // number of move_direction calls that changed the board
+    effective_move_count: u64,
+    // sum of the values of all tiles created by merges
+    score: u64,
 }
 
 impl Board {
     pub fn new(rows: usize, cols: usize) -> Self {
+        let seed = unsafe { core::arch::x86_64::_rdtsc() };
+        Self::new_with_seed(rows, cols, seed)
+    }
+
+    pub fn new_with_seed(rows: usize, cols: usize, seed: u64) -> Self {
         let mut tiles = Vec::with_capacity(rows * cols);
 
         for row in 0..rows {
@@ -50,13 +70,14 @@ impl Board {
             }
         }
 
-        let seed = unsafe { core::arch::x86_64::_rdtsc() };
-
         Self {
             rows,
             cols,
             tiles,
             rng: rand::rngs::SmallRng::seed_from_u64(seed),
+            move_count: 0,
+            effective_move_count: 0,
+            score: 0,
         }
     }
 
@@ -64,9 +85,81 @@ impl Board {
         for t in self.tiles.iter_mut() {
             t.val = None;
             t.changed = true;
+            t.merge_count = 0;
+            t.merged = false;
+        }
+        self.move_count = 0;
+        self.effective_move_count = 0;
+        self.score = 0;
+    }
+
+    #[allow(dead_code)]
+    pub fn move_count(&self) -> u64 {
+        self.move_count
+    }
+
+    pub fn effective_move_count(&self) -> u64 {
+        self.effective_move_count
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    // tiles returns all tiles row by row
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    // tile_at returns the tile at row and col and panics if either is out of
+    // range
+    pub fn tile_at(&self, row: usize, col: usize) -> &Tile {
+        assert!(
+            row < self.rows && col < self.cols,
+            "tile ({}, {}) out of range",
+            row,
+            col
+        );
+        &self.tiles[row * self.cols + col]
+    }
+
+    // fill_pattern sets all tiles from pattern, given row by row, and marks
+    // them as changed. The pattern must have the size of the board.
+    pub fn fill_pattern<R: AsRef<[Option<u64>]>>(&mut self, pattern: &[R]) {
+        assert_eq!(
+            pattern.len(),
+            self.rows,
+            "pattern needs one row per board row"
+        );
+        for row in pattern.iter() {
+            assert_eq!(
+                row.as_ref().len(),
+                self.cols,
+                "pattern row has the wrong length"
+            );
+        }
+        for t in self.tiles.iter_mut() {
+            t.val = pattern[t.row].as_ref()[t.col];
+            t.changed = true;
         }
     }
 
+    // values_row_major returns the values of the board row by row
+    pub fn values_row_major(&self) -> Vec<Vec<Option<u64>>> {
+        self.tiles
+            .chunks(self.cols)
+            .map(|row| row.iter().map(|t| t.val).collect())
+            .collect()
+    }
+
     pub fn has_changed(&self) -> bool {
         self.tiles.iter().any(|v| v.changed)
     }
@@ -79,10 +172,26 @@ impl Board {
         self.tiles.iter_mut().for_each(|v| v.changed = false)
     }
 
-    pub fn random_fill_empty_tile(&mut self) -> bool {
+    // mark_all_changed makes all tiles be drawn again
+    pub fn mark_all_changed(&mut self) {
+        self.tiles.iter_mut().for_each(|v| v.changed = true)
+    }
+
+    // clear_merged resets the merged flag and marks the affected tiles as
+    // changed, so they get drawn once more without the merge highlight
+    pub fn clear_merged(&mut self) {
+        for t in self.tiles.iter_mut().filter(|v| v.merged) {
+            t.merged = false;
+            t.changed = true;
+        }
+    }
+
+    // random_fill_empty_tile puts a 2 or 4 on a random empty tile and returns
+    // its (row, col)
+    pub fn random_fill_empty_tile(&mut self) -> Option<(usize, usize)> {
         let empty_tiles = self.tiles.iter().filter(|v| v.val.is_none()).count();
         if empty_tiles == 0 {
-            return false;
+            return None;
         }
 
         let random = (self.rng.next_u64() as usize) % empty_tiles;
@@ -100,7 +209,8 @@ impl Board {
 
         to_fill.val = Some(fill_val);
         to_fill.changed = true;
-        true
+        to_fill.merge_count = 0;
+        Some((to_fill.row, to_fill.col))
     }
 
     pub fn is_game_over(&self) -> bool {
@@ -150,7 +260,7 @@ impl Board {
         &self.tiles[idx]
     }
 
-    fn get_tile_mut(&mut self, dir: Direction, x: usize, y: usize) -> &mut Tile {
+    pub(crate) fn get_tile_mut(&mut self, dir: Direction, x: usize, y: usize) -> &mut Tile {
         let idx = self.pos(dir, x, y);
         &mut self.tiles[idx]
     }
@@ -162,6 +272,10 @@ impl Board {
         }
     }
 
+    pub fn occupied_tiles(&self) -> usize {
+        self.tiles.iter().filter(|v| v.val.is_some()).count()
+    }
+
     pub fn max_val(&self) -> u64 {
         if let Some(max) = self.tiles.iter().map(|v| v.val.unwrap_or(0)).max() {
             max
@@ -170,7 +284,50 @@ impl Board {
         }
     }
 
+    // serialize returns the tile values row by row, 8 little endian bytes
+    // each and 0 for an empty tile
+    #[allow(dead_code)]
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.tiles.len() * 8);
+        for t in self.tiles.iter() {
+            bytes.extend_from_slice(&t.val.unwrap_or(0).to_le_bytes());
+        }
+        bytes
+    }
+
+    // deserialize returns the square board stored by serialize, or None if
+    // bytes doesn't hold one. The rng can't be stored, the board gets a new
+    // seed.
+    #[allow(dead_code)]
+    pub fn deserialize(bytes: &[u8]) -> Option<Board> {
+        if bytes.len() % 8 != 0 {
+            return None;
+        }
+        let tiles = bytes.len() / 8;
+        let size = (1..=tiles).find(|s| s * s == tiles)?;
+        let mut board = Board::new(size, size);
+        for (t, chunk) in board.tiles.iter_mut().zip(bytes.chunks_exact(8)) {
+            let val = u64::from_le_bytes(chunk.try_into().ok()?);
+            // tiles are powers of two from 2 on
+            if val != 0 && (val == 1 || !val.is_power_of_two()) {
+                return None;
+            }
+            t.val = if val == 0 { None } else { Some(val) };
+            t.changed = true;
+        }
+        Some(board)
+    }
+
+    // preview_move returns a copy of the board after moving it in dir, the
+    // board itself is left as it is
+    pub fn preview_move(&self, dir: Direction) -> Board {
+        let mut board = self.clone();
+        board.move_direction(dir);
+        board
+    }
+
     pub fn move_direction(&mut self, dir: Direction) -> bool {
+        self.move_count += 1;
         let mut changed = false;
         let (x_dim, y_dim) = self.get_dimension_from_direction(dir);
         for x in 0..x_dim {
@@ -201,6 +358,7 @@ impl Board {
                 }
 
                 let val = cur.val.clone();
+                let merge_count = cur.merge_count;
                 // cur.changed = true;
                 changed = true;
 
@@ -209,19 +367,27 @@ impl Board {
                 if destination.val.is_none() {
                     destination.val = val;
                     destination.changed = true;
+                    destination.merge_count = merge_count;
                 } else {
                     destination.val = Some(val.unwrap() * 2);
                     destination.changed = true;
-                    has_merged_one = true
+                    destination.merge_count += 1;
+                    destination.merged = true;
+                    has_merged_one = true;
+                    self.score += val.unwrap() * 2;
                 }
                 // println!("  destination => {:?}", destination);
 
                 let cur = &mut self.get_tile_mut(dir, x, y);
                 cur.val = None;
                 cur.changed = true;
+                cur.merge_count = 0;
                 // println!("  self => {:?}", cur);
             }
         }
+        if changed {
+            self.effective_move_count += 1;
+        }
         changed
     }
 }
@@ -248,77 +414,287 @@ mod tests {
         assert_eq!(board.pos(Direction::Down, 2, 2), 2);
     }
 
+    #[test]
+    fn test_tile_at() {
+        let board = Board::new_with_seed(3, 5, 0);
+        assert_eq!(board.rows(), 3);
+        assert_eq!(board.cols(), 5);
+        assert_eq!(board.tiles().len(), 15);
+        for row in 0..3 {
+            for col in 0..5 {
+                let t = board.tile_at(row, col);
+                assert_eq!((t.row, t.col), (row, col));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tile_at_out_of_range() {
+        let board = Board::new_with_seed(3, 5, 0);
+        board.tile_at(0, 5);
+    }
+
+    #[test]
+    fn test_fill_pattern() {
+        let mut board = Board::new_with_seed(4, 4, 0);
+        let pattern = [
+            [Some(2), None, None, Some(4)],
+            [None, Some(8), None, None],
+            [None, None, Some(16), None],
+            [Some(32), None, None, Some(2048)],
+        ];
+
+        board.fill_pattern(&pattern);
+
+        assert_eq!(board.values_row_major(), pattern);
+        assert_eq!(board.get_tile_mut(Direction::Left, 0, 3).val, Some(4));
+        assert_eq!(board.get_tile_mut(Direction::Up, 0, 3).val, Some(32));
+        assert!(board.tiles.iter().all(|t| t.changed));
+    }
+
     #[test]
     fn test_move_simple() {
         let mut board = Board::new(3, 4);
-
-        board.get_tile_mut(Direction::Left, 0, 0).val = Some(2);
-        board.get_tile_mut(Direction::Left, 1, 1).val = Some(4);
-        board.get_tile_mut(Direction::Left, 2, 2).val = Some(8);
+        board.fill_pattern(&[
+            [Some(2), None, None, None],
+            [None, Some(4), None, None],
+            [None, None, Some(8), None],
+        ]);
 
         board.move_direction(Direction::Left);
 
-        assert_eq!(board.get_tile_mut(Direction::Left, 0, 0).val, Some(2));
-        assert_eq!(board.get_tile_mut(Direction::Left, 1, 0).val, Some(4));
-        assert_eq!(board.get_tile_mut(Direction::Left, 2, 0).val, Some(8));
+        assert_eq!(
+            board.values_row_major(),
+            [
+                [Some(2), None, None, None],
+                [Some(4), None, None, None],
+                [Some(8), None, None, None],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_score() {
+        let mut board = Board::new_with_seed(4, 4, 0);
+        board.fill_pattern(&[
+            [Some(2), Some(2), Some(4), Some(4)],
+            [Some(8), None, Some(8), None],
+            [None, None, None, None],
+            [None, None, None, None],
+        ]);
+
+        // one merge per line and move, the 4s stay apart
+        board.move_direction(Direction::Left);
+        assert_eq!(board.score(), 4 + 16);
+        // moves without merges don't score
+        board.move_direction(Direction::Down);
+        assert_eq!(board.score(), 20);
 
-        assert_eq!(board.get_tile_mut(Direction::Left, 1, 1).val, None);
-        assert_eq!(board.get_tile_mut(Direction::Left, 2, 2).val, None);
+        board.reset();
+        assert_eq!(board.score(), 0);
     }
 
     #[test]
     fn test_move_mutiple() {
         let mut board = Board::new(3, 5);
-
-        board.get_tile_mut(Direction::Right, 0, 0).val = None;
-        board.get_tile_mut(Direction::Right, 0, 1).val = Some(2);
-        board.get_tile_mut(Direction::Right, 0, 2).val = None;
-        board.get_tile_mut(Direction::Right, 0, 3).val = Some(4);
+        board.fill_pattern(&[
+            [None, Some(4), None, Some(2), None],
+            [None, None, None, None, None],
+            [None, None, None, None, None],
+        ]);
 
         board.move_direction(Direction::Right);
 
-        assert_eq!(board.get_tile_mut(Direction::Right, 0, 0).val, Some(2));
-        assert_eq!(board.get_tile_mut(Direction::Right, 0, 1).val, Some(4));
-        assert_eq!(board.get_tile_mut(Direction::Right, 0, 2).val, None);
-        assert_eq!(board.get_tile_mut(Direction::Right, 0, 3).val, None);
-        assert_eq!(board.get_tile_mut(Direction::Right, 0, 4).val, None);
+        assert_eq!(
+            board.values_row_major()[0],
+            [None, None, None, Some(4), Some(2)]
+        );
     }
 
     #[test]
     fn test_merge() {
         let mut board = Board::new(5, 3);
-
-        board.get_tile_mut(Direction::Up, 0, 0).val = None;
-        board.get_tile_mut(Direction::Up, 0, 1).val = Some(2);
-        board.get_tile_mut(Direction::Up, 0, 2).val = None;
-        board.get_tile_mut(Direction::Up, 0, 3).val = Some(2);
-        board.get_tile_mut(Direction::Up, 0, 4).val = Some(4);
+        board.fill_pattern(&[
+            [None, None, None],
+            [Some(2), None, None],
+            [None, None, None],
+            [Some(2), None, None],
+            [Some(4), None, None],
+        ]);
 
         board.move_direction(Direction::Up);
 
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 0).val, Some(4));
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 1).val, Some(4));
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 2).val, None);
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 3).val, None);
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 4).val, None);
+        assert_eq!(
+            board.values_row_major(),
+            [
+                [Some(4), None, None],
+                [Some(4), None, None],
+                [None, None, None],
+                [None, None, None],
+                [None, None, None],
+            ]
+        );
     }
 
     #[test]
     fn test_not_merge() {
         let mut board = Board::new(5, 3);
-
-        board.get_tile_mut(Direction::Up, 0, 0).val = None;
-        board.get_tile_mut(Direction::Up, 0, 1).val = Some(2);
-        board.get_tile_mut(Direction::Up, 0, 2).val = None;
-        board.get_tile_mut(Direction::Up, 0, 3).val = Some(4);
-        board.get_tile_mut(Direction::Up, 0, 4).val = Some(2);
+        board.fill_pattern(&[
+            [None, None, None],
+            [Some(2), None, None],
+            [None, None, None],
+            [Some(4), None, None],
+            [Some(2), None, None],
+        ]);
 
         board.move_direction(Direction::Up);
 
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 0).val, Some(2));
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 1).val, Some(4));
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 2).val, Some(2));
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 3).val, None);
-        assert_eq!(board.get_tile_mut(Direction::Up, 0, 4).val, None);
+        assert_eq!(
+            board.values_row_major(),
+            [
+                [Some(2), None, None],
+                [Some(4), None, None],
+                [Some(2), None, None],
+                [None, None, None],
+                [None, None, None],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_count() {
+        let mut board = Board::new_with_seed(4, 4, 0);
+        board.fill_pattern(&[
+            [Some(2), Some(2), None, None],
+            [None, None, None, None],
+            [None, None, None, None],
+            [None, None, None, None],
+        ]);
+
+        board.move_direction(Direction::Left);
+        assert_eq!(board.values_row_major()[0], [Some(4), None, None, None]);
+        assert_eq!(board.get_tile_mut(Direction::Left, 0, 0).merge_count, 1);
+        assert!(board.get_tile_mut(Direction::Left, 0, 0).merged);
+
+        board.clear_merged();
+        assert!(!board.get_tile_mut(Direction::Left, 0, 0).merged);
+
+        board.get_tile_mut(Direction::Left, 0, 3).val = Some(4);
+        board.move_direction(Direction::Left);
+        assert_eq!(board.values_row_major()[0], [Some(8), None, None, None]);
+        assert_eq!(board.get_tile_mut(Direction::Left, 0, 0).merge_count, 2);
+
+        // moving a tile keeps its merge count
+        board.move_direction(Direction::Right);
+        assert_eq!(board.values_row_major()[0], [None, None, None, Some(8)]);
+        assert_eq!(board.get_tile_mut(Direction::Left, 0, 3).merge_count, 2);
+        assert_eq!(board.get_tile_mut(Direction::Left, 0, 0).merge_count, 0);
+    }
+
+    #[test]
+    fn test_move_count() {
+        let mut board = Board::new_with_seed(4, 4, 0);
+        board.fill_pattern(&[
+            [Some(2), None, None, None],
+            [None, None, None, None],
+            [None, None, None, None],
+            [None, None, None, None],
+        ]);
+
+        assert!(!board.move_direction(Direction::Left));
+        assert_eq!(board.move_count(), 1);
+        assert_eq!(board.effective_move_count(), 0);
+
+        assert!(board.move_direction(Direction::Right));
+        assert_eq!(board.move_count(), 2);
+        assert_eq!(board.effective_move_count(), 1);
+
+        board.reset();
+        assert_eq!(board.move_count(), 0);
+        assert_eq!(board.effective_move_count(), 0);
+    }
+
+    fn fuzz_move_direction(seed: u64) {
+        let mut board = Board::new_with_seed(4, 4, seed);
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+
+        for (i, tile) in board.tiles.iter_mut().enumerate() {
+            if (i as u64 + seed) % 3 == 0 {
+                tile.val = Some(2 << ((i as u64 * seed) % 4));
+            }
+        }
+
+        for _ in 0..20 {
+            let total: u64 = board.tiles.iter().filter_map(|t| t.val).sum();
+            let occupied = board.tiles.iter().filter(|t| t.val.is_some()).count();
+            let max = board.max_val();
+
+            let dir: Direction = rng.gen();
+            if board.move_direction(dir) {
+                board.random_fill_empty_tile();
+            }
+
+            let new_total: u64 = board.tiles.iter().filter_map(|t| t.val).sum();
+            let new_occupied = board.tiles.iter().filter(|t| t.val.is_some()).count();
+            assert!(new_total >= total, "seed {}: total decreased", seed);
+            assert!(
+                new_occupied <= occupied + 1,
+                "seed {}: too many tiles",
+                seed
+            );
+            assert!(board.max_val() <= 2 * max, "seed {}: tile too large", seed);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_move_direction() {
+        for seed in 0..1000 {
+            fuzz_move_direction(seed);
+        }
+    }
+
+    #[test]
+    fn test_preview_move() {
+        let mut board = Board::new_with_seed(4, 4, 0);
+        board.fill_pattern(&[
+            [Some(2), None, None, Some(2)],
+            [None, None, None, None],
+            [None, None, None, None],
+            [None, None, None, None],
+        ]);
+        let before = board.values_row_major();
+        let preview = board.preview_move(Direction::Left);
+        assert_eq!(preview.values_row_major()[0], [Some(4), None, None, None]);
+        assert_eq!(preview.effective_move_count(), 1);
+        assert_eq!(board.values_row_major(), before);
+        assert_eq!(board.move_count(), 0);
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let mut board = Board::new_with_seed(4, 4, 0);
+        board.fill_pattern(&[
+            [Some(2), None, None, Some(4)],
+            [None, Some(2048), None, None],
+            [None, None, Some(65536), None],
+            [Some(8), None, None, Some(2)],
+        ]);
+        let bytes = board.serialize();
+        assert_eq!(bytes.len(), 16 * 8);
+        assert_eq!(bytes[..8], 2u64.to_le_bytes());
+        assert_eq!(bytes[8..16], [0; 8]);
+
+        let restored = Board::deserialize(&bytes).unwrap();
+        assert_eq!(restored.values_row_major(), board.values_row_major());
+        assert_eq!(restored.serialize(), bytes);
+
+        // the tile count must fill a square board
+        assert!(Board::deserialize(&[]).is_none());
+        assert!(Board::deserialize(&bytes[..15 * 8]).is_none());
+        assert!(Board::deserialize(&bytes[..9 * 8]).is_some());
+        let mut invalid = bytes.clone();
+        invalid[0] = 3;
+        assert!(Board::deserialize(&invalid).is_none());
     }
 }
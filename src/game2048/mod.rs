@@ -1,4 +1,5 @@
 mod board;
+mod util;
 mod world;
 
 pub  use world::World;
@@ -0,0 +1,37 @@
+use crate::world::CharsBuf;
+use core::fmt::Write;
+
+// format_tile_value returns the text of a tile value that fits in four
+// characters, values from 1000 on are abbreviated as 1K, 2K, ... and 1M, 2M,
+// ... as the tiles are powers of two
+pub fn format_tile_value(v: u64) -> CharsBuf<4> {
+    let mut text = CharsBuf::<4>::new();
+    if v < 1000 {
+        write!(text, "{}", v).unwrap();
+    } else if v / 1024 < 1000 {
+        write!(text, "{}K", v / 1024).unwrap();
+    } else {
+        write!(text, "{}M", v / (1024 * 1024)).unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn formatted(v: u64) -> String {
+        format_tile_value(v).chars().iter().collect()
+    }
+
+    #[test]
+    fn test_format_tile_value() {
+        assert_eq!(formatted(2), "2");
+        assert_eq!(formatted(128), "128");
+        assert_eq!(formatted(1024), "1K");
+        assert_eq!(formatted(16384), "16K");
+        assert_eq!(formatted(524288), "512K");
+        assert_eq!(formatted(1048576), "1M");
+    }
+}
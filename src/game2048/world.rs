@@ -1,94 +1,440 @@
 use crate::display::{Color, Display};
-use crate::game2048::board;
-use crate::serial_println;
-use crate::world::{CharsBuf, Direction, Game, GameState, ScreenPos};
+use crate::game2048::{board, util};
+use crate::world::{transition, CharsBuf, Direction, Game, GameState, ScreenPos};
+use crate::{serial_print, serial_println};
 use core::fmt::Write;
 use pc_keyboard::{DecodedKey, KeyCode};
 
-const BOARD_SIZE: usize = 4;
 const MARGIN_PIXELS: usize = 4; // pixels
-const BOARDER_PIXELS: usize = 4;
+const BORDER_PIXELS: usize = 4;
+
+// BORDER_COLOR is the color of the frame around the board
+pub const BORDER_COLOR: Color = Color::RGB32(0xeee4da);
+
+// target_tile returns the tile that wins the game on a board_size x
+// board_size board, 2048 on the classic 4x4 board
+fn target_tile(board_size: usize) -> u64 {
+    1 << (2 * board_size + 3)
+}
+
+// corner_score rates a board for the autoplay, every tile counts the more the
+// closer it is to the top left corner
+fn corner_score(board: &board::Board) -> u64 {
+    let max_dist = board.rows() + board.cols() - 2;
+    board
+        .tiles()
+        .iter()
+        .map(|t| t.val.unwrap_or(0) << (2 * (max_dist - t.row - t.col)))
+        .sum()
+}
+
+// autoplay_direction returns the move with the best corner_score among the
+// ones that change the board
+fn autoplay_direction(board: &board::Board) -> Option<Direction> {
+    let mut best = None;
+    let mut best_score = 0;
+    for dir in [
+        Direction::Left,
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+    ]
+    .iter()
+    {
+        let preview = board.preview_move(*dir);
+        if preview.effective_move_count() == board.effective_move_count() {
+            continue;
+        }
+        let score = corner_score(&preview);
+        // a moved board has tiles, so its score is never 0
+        if score > best_score {
+            best = Some(*dir);
+            best_score = score;
+        }
+    }
+    best
+}
+
+// UndoState is what undo goes back to: the board before the last move and
+// the stats that went with it
+struct UndoState {
+    board: board::Board,
+    total_merges: u64,
+    largest_tile_seen: u64,
+}
 
 pub struct World {
     board: board::Board,
+    // the number of tiles per row and column
+    board_size: usize,
+    // the tile value that wins the game
+    target_tile: u64,
     game_over: bool,
+    transition_ticks: Option<u8>,
     width: usize,
     height: usize,
     tile_size: usize,
-    boarder_drawn: bool,
+    border_drawn: bool,
     result_drawn: bool,
+    // won is set once the target tile is reached, the game waits for 'c'
+    // until continue_playing is set as well
+    won: bool,
+    continue_playing: bool,
+    total_merges: u64,
+    largest_tile_seen: u64,
+    show_stats: bool,
+    stats_dirty: bool,
+    // the score header above the board needs to be redrawn
+    score_dirty: bool,
+    // the best score of the previous games, kept across resets
+    best_score: u64,
+    // the number of moves that changed the board in this game
+    move_count: u64,
+    // the state before the last move, for a single undo
+    undo_stack: Option<UndoState>,
+    // frames left to highlight the last spawned tile
+    new_tile_pulse: u8,
+    last_spawned_pos: Option<(usize, usize)>,
+    // frames left of the slide animation of the last move
+    animation_ticks: u8,
+    // the moves are picked by autoplay_direction instead of the keyboard
+    autoplay: bool,
 }
 
+// NEW_TILE_PULSE_FRAMES is how many frames a newly spawned tile is highlighted
+const NEW_TILE_PULSE_FRAMES: u8 = 6;
+
+// ANIMATION_TICKS is how many frames the changed tiles grow back to full size
+// after a move
+const ANIMATION_TICKS: u8 = 3;
+
+// EMPTY_TILE_COLOR is the color of a cell without a tile
+const EMPTY_TILE_COLOR: Color = Color::RGB32(0x323846);
+
 impl World {
-    pub fn new(width: usize, height: usize) -> Self {
-        let tile_size = (core::cmp::min(width, height) * 8 / 10 - MARGIN_PIXELS * (BOARD_SIZE + 1))
-            / BOARD_SIZE;
+    // new returns a game on a board_size x board_size board, board_size is
+    // between 3 and 6
+    pub fn new(width: usize, height: usize, board_size: usize) -> Self {
+        assert!(
+            (3..=6).contains(&board_size),
+            "unsupported board size {}",
+            board_size
+        );
+        let tile_size = (core::cmp::min(width, height) * 8 / 10 - MARGIN_PIXELS * (board_size + 1))
+            / board_size;
 
         // make sure tile size is even
         let tile_size = tile_size - tile_size % 2;
 
         Self {
-            board: board::Board::new(BOARD_SIZE, BOARD_SIZE),
+            board: board::Board::new(board_size, board_size),
+            board_size,
+            target_tile: target_tile(board_size),
             game_over: false,
+            transition_ticks: None,
             width,
             height,
             tile_size,
-            boarder_drawn: false,
+            border_drawn: false,
             result_drawn: false,
+            won: false,
+            continue_playing: false,
+            total_merges: 0,
+            largest_tile_seen: 0,
+            show_stats: false,
+            stats_dirty: false,
+            score_dirty: true,
+            best_score: 0,
+            move_count: 0,
+            undo_stack: None,
+            new_tile_pulse: 0,
+            last_spawned_pos: None,
+            animation_ticks: 0,
+            autoplay: false,
         }
     }
 
-    fn draw_tile(&self, tile: &board::Tile, display: &mut Display) {
+    // play_move makes a move of the player and spawns a new tile if the board
+    // changed
+    fn play_move(&mut self, direction: Direction) {
+        let before = UndoState {
+            board: self.board.clone(),
+            total_merges: self.total_merges,
+            largest_tile_seen: self.largest_tile_seen,
+        };
+        if self.apply_move(direction) {
+            self.undo_stack = Some(before);
+            self.move_count += 1;
+            self.score_dirty = true;
+            self.animation_ticks = ANIMATION_TICKS;
+            self.spawn_tile();
+        }
+
+        self.game_over = self.board.is_game_over();
+    }
+
+    // apply_move moves the board and updates the statistics
+    fn apply_move(&mut self, direction: Direction) -> bool {
+        let tiles_before = self.board.occupied_tiles();
+        if !self.board.move_direction(direction) {
+            return false;
+        }
+        // every merge turns two tiles into one and scores
+        let merges = (tiles_before - self.board.occupied_tiles()) as u64;
+        self.total_merges += merges;
+        self.score_dirty |= merges > 0;
+        self.stats_dirty = self.show_stats;
+        true
+    }
+
+    fn draw_stats(&self, display: &mut Display) {
+        let y = self.height - self.height / 20;
+        display.draw_rect(0, y, self.width, 8, Color::Black);
+        if !self.show_stats {
+            return;
+        }
+
+        let mut stats = CharsBuf::<64>::new();
+        write!(
+            stats,
+            "Moves: {}  Merges: {}  Best Tile: {}",
+            self.board.effective_move_count(),
+            self.total_merges,
+            self.largest_tile_seen
+        )
+        .unwrap();
+
+        // the numbers are bold, which makes each digit one pixel wider
+        let (text_width, _) = display.measure_chars(stats.chars());
+        let digits = stats.chars().iter().filter(|c| c.is_ascii_digit()).count();
+        display.set_xy(self.width.saturating_sub(text_width + digits) / 2, y);
+        for c in stats.chars() {
+            display.set_bold(c.is_ascii_digit());
+            display.write_char_colored(*c, Color::White, Color::Black);
+        }
+        display.set_bold(false);
+    }
+
+    // undo goes back to the board and stats before the last move, once. The
+    // move no longer counts either.
+    fn undo(&mut self) {
+        if let Some(before) = self.undo_stack.take() {
+            self.board = before.board;
+            self.total_merges = before.total_merges;
+            self.largest_tile_seen = before.largest_tile_seen;
+            self.board.mark_all_changed();
+            self.move_count = self.move_count.saturating_sub(1);
+            self.score_dirty = true;
+            self.stats_dirty = self.show_stats;
+            self.new_tile_pulse = 0;
+            self.last_spawned_pos = None;
+            self.animation_ticks = 0;
+        }
+    }
+
+    // draw_message writes chars centered on the message line below the board
+    fn draw_message(&self, display: &mut Display, chars: &[char]) {
+        let (text_width, _) = display.measure_chars(chars);
+        let x = self.width.saturating_sub(text_width) / 2;
+        let y = self.height - self.height / 10;
+
+        display.clear_rect(0, y, self.width, 8);
+        display.set_xy(x, y);
+        for c in chars {
+            display.write_char_colored(*c, Color::White, Color::Black);
+        }
+    }
+
+    // draw_score writes the score and the best score centered in the header
+    // between the top of the screen and the board
+    fn draw_score(&self, display: &mut Display) {
+        let score = self.board.score();
+        let (_, board_y, _, _) = self.board_rect();
+        let y = (board_y / 2).saturating_sub(4);
+        let mut text = CharsBuf::<64>::new();
+        let best = self.best_score.max(score);
+        write!(
+            text,
+            "Moves: {}  Score: {}  Best: {}",
+            self.move_count, score, best
+        )
+        .unwrap();
+        if self.autoplay {
+            write!(text, "  [Auto]").unwrap();
+        }
+        display.draw_rect(0, y, self.width, 8, Color::Black);
+        let (text_width, _) = display.measure_chars(text.chars());
+        let x = self.width.saturating_sub(text_width) / 2;
+        display.draw_text_at(x, y, text.chars(), Color::White, Color::Black);
+    }
+
+    // tile_pos returns the top left corner of the tile at row, col
+    fn tile_pos(&self, row: usize, col: usize) -> ScreenPos {
         let center = ScreenPos::new(self.width, self.height).center();
-        let off = (BOARD_SIZE * self.tile_size + (BOARD_SIZE + 1) * MARGIN_PIXELS) / 2;
+        let off = (self.board_size * self.tile_size + (self.board_size + 1) * MARGIN_PIXELS) / 2;
 
         let left_top = center.left(off).up(off);
 
-        let pos = left_top
+        left_top
             .right(MARGIN_PIXELS)
-            .right_blocks(tile.col, self.tile_size + MARGIN_PIXELS)
+            .right_blocks(col, self.tile_size + MARGIN_PIXELS)
             .down(MARGIN_PIXELS)
-            .down_blocks(tile.row, self.tile_size + MARGIN_PIXELS);
+            .down_blocks(row, self.tile_size + MARGIN_PIXELS)
+    }
+
+    // board_rect returns the (x, y, w, h) rectangle covered by the tiles and
+    // the margins between them
+    fn board_rect(&self) -> (usize, usize, usize, usize) {
+        let left_top = self.tile_pos(0, 0).left(MARGIN_PIXELS).up(MARGIN_PIXELS);
+        let len = self.board_size * self.tile_size + (self.board_size + 1) * MARGIN_PIXELS;
+        (left_top.x, left_top.y, len, len)
+    }
+
+    // spawn_tile fills a random empty tile and highlights it for a few frames
+    fn spawn_tile(&mut self) {
+        if let Some(pos) = self.board.random_fill_empty_tile() {
+            self.last_spawned_pos = Some(pos);
+            self.new_tile_pulse = NEW_TILE_PULSE_FRAMES;
+        }
+    }
+
+    // draw_new_tile_pulse outlines the last spawned tile while the pulse lasts
+    // and has it redrawn normally when the pulse is over
+    fn draw_new_tile_pulse(&mut self, display: &mut Display) {
+        if self.new_tile_pulse == 0 {
+            return;
+        }
+        self.new_tile_pulse -= 1;
+        if let Some((row, col)) = self.last_spawned_pos {
+            if self.new_tile_pulse == 0 {
+                self.board.get_tile_mut(Direction::Left, row, col).changed = true;
+            } else {
+                let pos = self.tile_pos(row, col);
+                display.draw_rect_border(
+                    pos.x,
+                    pos.y,
+                    self.tile_size,
+                    self.tile_size,
+                    Color::White,
+                );
+            }
+        }
+    }
+
+    fn draw_tile(&self, tile: &board::Tile, display: &mut Display) {
+        let pos = self.tile_pos(tile.row, tile.col);
 
         let color = tile_color2(tile);
-        display.draw_rect(pos.x, pos.y, self.tile_size, self.tile_size, color);
+        if self.animation_ticks > 0 && tile.val.is_some() {
+            self.draw_tile_animated(tile, pos, color, display);
+            return;
+        }
+
+        display.draw_gradient_rect_v(
+            pos.x,
+            pos.y,
+            self.tile_size,
+            self.tile_size,
+            color,
+            color.blend(Color::Black, 48),
+        );
+
+        // the more merges produced a tile, the brighter its border
+        if tile.merged {
+            display.draw_rect_outline(
+                pos.x,
+                pos.y,
+                self.tile_size,
+                self.tile_size,
+                Color::White,
+                2,
+            );
+        } else if tile.val.is_some() && tile.merge_count > 0 {
+            let brightness = (tile.merge_count.min(6) * 32) as u8;
+            let border = Color::RGB((brightness, brightness, brightness));
+            display.draw_rect_outline(pos.x, pos.y, self.tile_size, self.tile_size, border, 2);
+        }
 
         if let Some(val) = &tile.val {
-            let mut num = CharsBuf::<4>::new();
-            write!(num, "{}", val);
+            let num = util::format_tile_value(*val);
 
-            let x = pos.x + (self.tile_size - 8 * num.len()) / 2;
+            let (text_width, _) = display.measure_chars(num.chars());
+            let x = pos.x + self.tile_size.saturating_sub(text_width) / 2;
             let y = pos.y + (self.tile_size - 8) / 2;
             display.set_xy(x, y);
+            // the text sits in the middle of the gradient
+            let background = color.blend(Color::Black, 24);
+            let foreground = if color.is_dark() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            // values without a unit are written in large digits when they
+            // fit into the tile
+            let large_width = num.chars().len() * 9;
+            if *val < 1000 && self.tile_size >= 32 && large_width + 8 <= self.tile_size {
+                let x = pos.x + (self.tile_size - large_width) / 2;
+                let y = pos.y + (self.tile_size - 16) / 2;
+                let screen_background = display.background_color;
+                display.background_color = background;
+                display.draw_number_large(x, y, *val as usize, foreground);
+                display.background_color = screen_background;
+                return;
+            }
             for c in num.chars() {
-                display.write_char_colored(*c, Color::White, color);
+                display.write_char_colored(*c, foreground, background);
             }
         }
     }
 
-    fn draw_boarder(&self, display: &mut Display) {
-        let color = Color::RGB32(0xeee4da);
-
-        let half = BOARD_SIZE / 2;
-        let boarder_len =
-            BOARD_SIZE * self.tile_size + (BOARD_SIZE + 1) * MARGIN_PIXELS + BOARDER_PIXELS * 2;
-        let off = boarder_len / 2;
-
-        let center = ScreenPos::new(self.width, self.height).center();
-        let left_top = center.left(off).up(off);
-        display.draw_rect(left_top.x, left_top.y, boarder_len, MARGIN_PIXELS, color);
-        display.draw_rect(left_top.x, left_top.y, MARGIN_PIXELS, boarder_len, color);
-
-        let right_top = center.right(off).up(off).left(MARGIN_PIXELS);
-        display.draw_rect(right_top.x, right_top.y, MARGIN_PIXELS, boarder_len, color);
+    // draw_tile_animated draws a frame of the slide animation: merged tiles
+    // flash white first, then the tile grows from the center of its cell
+    fn draw_tile_animated(
+        &self,
+        tile: &board::Tile,
+        pos: ScreenPos,
+        color: Color,
+        display: &mut Display,
+    ) {
+        let size = self.tile_size;
+        if tile.merged && self.animation_ticks == ANIMATION_TICKS {
+            display.draw_rect(pos.x, pos.y, size, size, Color::White);
+            return;
+        }
 
-        let left_bot = center.left(off).down(off).up(MARGIN_PIXELS);
-        display.draw_rect(left_bot.x, left_bot.y, boarder_len, MARGIN_PIXELS, color);
+        display.draw_gradient_rect_v(
+            pos.x,
+            pos.y,
+            size,
+            size,
+            EMPTY_TILE_COLOR,
+            EMPTY_TILE_COLOR.blend(Color::Black, 48),
+        );
+        let scaled =
+            size * (ANIMATION_TICKS - self.animation_ticks) as usize / ANIMATION_TICKS as usize;
+        let off = (size - scaled) / 2;
+        display.draw_gradient_rect_v(
+            pos.x + off,
+            pos.y + off,
+            scaled,
+            scaled,
+            color,
+            color.blend(Color::Black, 48),
+        );
+    }
 
-        serial_println!("BOARDER: {}", boarder_len);
-        serial_println!("BOARDER: left_top: {:?}", left_top);
-        serial_println!("BOARDER: right_top: {:?}", right_top);
-        serial_println!("BOARDER: left_bot: {:?}", left_bot);
+    // draw_border frames the board with BORDER_PIXELS wide edges right
+    // outside of board_rect, so the frame closes around the outer margin
+    fn draw_border(&self, display: &mut Display) {
+        let (x, y, len, _) = self.board_rect();
+        display.draw_rect_outline(
+            x.saturating_sub(BORDER_PIXELS),
+            y.saturating_sub(BORDER_PIXELS),
+            len + BORDER_PIXELS * 2,
+            len + BORDER_PIXELS * 2,
+            BORDER_COLOR,
+            BORDER_PIXELS,
+        );
     }
 }
 
@@ -114,7 +460,7 @@ fn tile_color(tile: &board::Tile) -> Color {
 // color scheme from https://github.com/dev-family/wasm-204://github.com/dev-family/wasm-2048
 fn tile_color2(tile: &board::Tile) -> Color {
     match tile.val {
-        None => Color::RGB32(0x323846),
+        None => EMPTY_TILE_COLOR,
         Some(2) => Color::RGB32(0xe91e63),
         Some(4) => Color::RGB32(0xe91e1f),
         Some(8) => Color::RGB32(0xe9601e),
@@ -132,31 +478,73 @@ fn tile_color2(tile: &board::Tile) -> Color {
 
 impl Game for World {
     fn reset(&mut self, width: usize, height: usize) {
+        self.best_score = self.best_score.max(self.board.score());
         self.board.reset();
 
         self.board.random_fill_empty_tile();
         self.board.random_fill_empty_tile();
         self.game_over = false;
+        self.transition_ticks = None;
         self.result_drawn = false;
-        self.boarder_drawn = false;
+        self.won = false;
+        self.continue_playing = false;
+        self.border_drawn = false;
+        self.total_merges = 0;
+        self.largest_tile_seen = 0;
+        self.stats_dirty = true;
+        self.score_dirty = true;
+        self.move_count = 0;
+        self.undo_stack = None;
+        self.new_tile_pulse = 0;
+        self.last_spawned_pos = None;
+        self.animation_ticks = 0;
     }
 
-    // step moves the snake one step forward
+    // step makes the next autoplay move once the last one is drawn and checks
+    // for the end of the game
     fn step(&mut self) -> GameState {
-        if self.game_over {
-            return GameState::Live;
+        let waiting = self.won && !self.continue_playing;
+        if self.autoplay && !self.game_over && !waiting && !self.board.has_changed() {
+            if let Some(direction) = autoplay_direction(&self.board) {
+                self.play_move(direction);
+            }
         }
-
-        if self.board.is_game_over() || self.board.max_val() == 2048 {
+        if self.board.is_game_over() {
             self.game_over = true;
-            return GameState::Live;
+        }
+        if !self.won && self.board.max_val() >= self.target_tile {
+            self.won = true;
+        }
+
+        if self.game_over {
+            return transition(&mut self.transition_ticks);
         }
 
         GameState::Live
     }
 
     fn on_keypress(&mut self, key: DecodedKey) {
-        if self.game_over || self.board.has_changed() {
+        if key == DecodedKey::Unicode('i') {
+            self.show_stats = !self.show_stats;
+            self.stats_dirty = true;
+            return;
+        }
+
+        if key == DecodedKey::Unicode('a') || key == DecodedKey::Unicode('A') {
+            self.autoplay = !self.autoplay;
+            self.score_dirty = true;
+            return;
+        }
+
+        if self.game_over || self.board.has_changed() || self.autoplay {
+            return;
+        }
+
+        // after a win only 'c' goes on, 'r' is handled by the world
+        if self.won && !self.continue_playing {
+            if key == DecodedKey::Unicode('c') || key == DecodedKey::Unicode('C') {
+                self.continue_playing = true;
+            }
             return;
         }
 
@@ -168,48 +556,384 @@ impl Game for World {
             _ => None,
         };
 
-        if direction.is_some() {
-            if self.board.move_direction(direction.unwrap()) {
-                self.board.random_fill_empty_tile();
-            }
+        if key == DecodedKey::Unicode('u') || key == DecodedKey::Unicode('U') {
+            self.undo();
+            return;
+        }
 
-            self.game_over = self.board.is_game_over();
+        if let Some(direction) = direction {
+            self.play_move(direction);
         }
     }
 
     fn draw(&mut self, display: &mut Display) {
-        /*
-        if !self.boarder_drawn {
-            self.draw_boarder(display);
-            self.boarder_drawn = true;
+        if !self.border_drawn {
+            self.draw_border(display);
+            self.border_drawn = true;
         }
-        */
 
-        if self.game_over {
-            if !self.result_drawn {
-                let mut result = CharsBuf::<128>::new();
-                let score = self.board.max_val();
-                if score >= 2048 {
-                    write!(result, "Congratulation! Press 'r' to restart.").unwrap();
-                } else {
-                    write!(result, "Game over! Press 'r' to restart.").unwrap();
-                }
+        let waiting = self.won && !self.continue_playing;
+        if (self.game_over || waiting) && !self.result_drawn {
+            let mut result = CharsBuf::<128>::new();
+            if self.game_over && self.won {
+                write!(result, "Congratulation! Press 'r' to restart.").unwrap();
+            } else if self.game_over {
+                write!(result, "Game over! Press 'r' to restart.").unwrap();
+            } else {
+                write!(result, "You win! Press 'c' to continue or 'r' to restart.").unwrap();
+            }
+            self.draw_message(display, result.chars());
+            self.result_drawn = true;
+        } else if !self.game_over && !waiting && self.result_drawn {
+            // the win message goes away when playing on
+            self.draw_message(display, &[]);
+            self.result_drawn = false;
+        }
 
-                let x = (self.width - 8 * result.len()) / 2;
-                let y = self.height - self.height / 10;
+        // keep the tiles inside the board
+        let (x, y, w, h) = self.board_rect();
+        display.set_clip_rect(x, y, w, h);
+        for tile in self.board.tiles_need_redraw() {
+            self.draw_tile(tile, display);
+        }
+        if self.animation_ticks > 0 {
+            // the changed tiles are drawn again until they are full size
+            self.animation_ticks -= 1;
+        } else {
+            self.board.clear_changed();
+            // redraw merged tiles without highlight in the next frame
+            self.board.clear_merged();
+        }
+        self.draw_new_tile_pulse(display);
+        display.clear_clip();
 
-                display.set_xy(x, y);
-                for c in result.chars() {
-                    display.write_char_colored(*c, Color::White, Color::Black);
+        let max_val = self.board.max_val();
+        if max_val > self.largest_tile_seen {
+            self.largest_tile_seen = max_val;
+            self.stats_dirty = self.show_stats;
+        }
+        if self.stats_dirty {
+            self.draw_stats(display);
+            self.stats_dirty = false;
+        }
+        if self.score_dirty {
+            self.draw_score(display);
+            self.score_dirty = false;
+        }
+    }
+
+    fn score(&self) -> u64 {
+        self.board.score()
+    }
+
+    fn serial_dump(&self) {
+        for row in 0..self.board.rows() {
+            for col in 0..self.board.cols() {
+                match self.board.tile_at(row, col).val {
+                    Some(v) => serial_print!("{:>6}", v),
+                    None => serial_print!("{:>6}", "."),
                 }
-                self.result_drawn = true;
             }
+            serial_println!();
         }
+    }
+}
 
-        for tile in self.board.tiles_need_redraw() {
-            self.draw_tile(tile, display);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::tests::pixel;
+    use crate::world::TRANSITION_TICKS;
+
+    #[test]
+    fn test_stats_increment() {
+        let mut world = World::new(640, 480, 4);
+        world.board.reset();
+        world.board.clear_changed();
+        world.board.get_tile_mut(Direction::Left, 0, 0).val = Some(2);
+        world.board.get_tile_mut(Direction::Left, 0, 1).val = Some(4);
+
+        // nothing can move to the left
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.board.effective_move_count(), 0);
+
+        world.board.get_tile_mut(Direction::Left, 1, 1).val = Some(4);
+        world.board.get_tile_mut(Direction::Left, 1, 3).val = Some(4);
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.board.effective_move_count(), 1);
+        assert_eq!(world.total_merges, 1);
+        assert_eq!(world.score(), 8);
+        assert!(world.score_dirty);
+
+        world.reset(640, 480);
+        assert_eq!(world.board.effective_move_count(), 0);
+        assert_eq!(world.total_merges, 0);
+        assert_eq!(world.score(), 0);
+        // the best score survives the reset
+        assert_eq!(world.best_score, 8);
+        world.reset(640, 480);
+        assert_eq!(world.best_score, 8);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut world = World::new(640, 480, 4);
+        world.reset(640, 480);
+        world.board.clear_changed();
+        let start = world.board.values_row_major();
+        // undo without a move does nothing
+        world.on_keypress(DecodedKey::Unicode('u'));
+        assert_eq!(world.board.values_row_major(), start);
+
+        let mut moved = (start.clone(), KeyCode::ArrowLeft);
+        for key in [KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowUp].iter() {
+            world.on_keypress(DecodedKey::RawKey(*key));
+            moved = (world.board.values_row_major(), *key);
+            if moved.0 != start {
+                break;
+            }
+        }
+        assert_ne!(moved.0, start);
+        world.board.clear_changed();
+
+        world.on_keypress(DecodedKey::Unicode('u'));
+        assert_eq!(world.board.values_row_major(), start);
+        assert!(world.board.tiles().iter().all(|t| t.changed));
+        // only one move can be undone
+        assert!(world.undo_stack.is_none());
+
+        // the rng is restored too, the same move spawns the same tile
+        world.board.clear_changed();
+        world.on_keypress(DecodedKey::RawKey(moved.1));
+        assert_eq!(world.board.values_row_major(), moved.0);
+    }
+
+    #[test]
+    fn test_undo_restores_stats() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480, 4);
+        world.board.reset();
+        world.board.get_tile_mut(Direction::Left, 0, 0).val = Some(4);
+        world.board.get_tile_mut(Direction::Left, 0, 1).val = Some(4);
+        world.draw(&mut display);
+        assert_eq!((world.total_merges, world.largest_tile_seen), (0, 4));
+
+        world.board.clear_changed();
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        world.draw(&mut display);
+        assert_eq!((world.total_merges, world.largest_tile_seen), (1, 8));
+
+        world.board.clear_changed();
+        world.on_keypress(DecodedKey::Unicode('u'));
+        assert_eq!((world.total_merges, world.largest_tile_seen), (0, 4));
+        assert_eq!(world.move_count, 0);
+        world.draw(&mut display);
+        assert_eq!(world.largest_tile_seen, 4);
+    }
+
+    #[test]
+    fn test_new_tile_pulse() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480, 4);
+        world.board.reset();
+        world.board.clear_changed();
+        world.board.get_tile_mut(Direction::Left, 0, 3).val = Some(2);
+
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.new_tile_pulse, NEW_TILE_PULSE_FRAMES);
+        let (row, col) = world.last_spawned_pos.unwrap();
+        assert!(world
+            .board
+            .get_tile_mut(Direction::Left, row, col)
+            .val
+            .is_some());
+
+        for _ in 0..NEW_TILE_PULSE_FRAMES {
+            world.draw(&mut display);
+        }
+        assert_eq!(world.new_tile_pulse, 0);
+        // the tile is redrawn without the highlight in the next frame
+        assert!(world.board.get_tile_mut(Direction::Left, row, col).changed);
+    }
+
+    #[test]
+    fn test_board_sizes() {
+        assert_eq!(target_tile(3), 512);
+        assert_eq!(target_tile(4), 2048);
+        assert_eq!(target_tile(6), 32768);
+        for size in 3..=6 {
+            let world = World::new(640, 480, size);
+            assert_eq!(world.board.tiles().len(), size * size);
+            // the board fits on the screen
+            let (x, y, w, h) = world.board_rect();
+            assert!(x + w <= 640 && y + h <= 480);
+        }
+    }
+
+    #[test]
+    fn test_win_on_target_tile() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480, 3);
+        world.reset(640, 480);
+        world.board.get_tile_mut(Direction::Left, 0, 0).val = Some(256);
+        assert_eq!(world.step(), GameState::Live);
+        assert!(!world.won);
+        world.board.get_tile_mut(Direction::Left, 0, 0).val = Some(512);
+        world.board.clear_changed();
+        assert_eq!(world.step(), GameState::Live);
+        assert!(world.won);
+        world.draw(&mut display);
+        assert!(world.result_drawn);
+
+        // the board is frozen until 'c'
+        let tiles = world.board.values_row_major();
+        for key in [KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowDown].iter() {
+            world.on_keypress(DecodedKey::RawKey(*key));
         }
+        assert_eq!(world.board.values_row_major(), tiles);
+
+        world.on_keypress(DecodedKey::Unicode('c'));
+        assert!(world.continue_playing);
+        world.draw(&mut display);
+        assert!(!world.result_drawn);
+        assert_eq!(world.step(), GameState::Live);
+
+        // a stuck board still ends the game
+        world.game_over = true;
+        assert_eq!(world.step(), GameState::Transitioning(TRANSITION_TICKS));
+    }
+
+    #[test]
+    fn test_border_closes_around_board() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480, 4);
+        world.reset(640, 480);
+        world.draw(&mut display);
+        assert!(world.border_drawn);
+
+        let border = (0xee, 0xe4, 0xda);
+        let (x, y, len, _) = world.board_rect();
+        let (left, top) = (x - BORDER_PIXELS, y - BORDER_PIXELS);
+        let (right, bottom) = (x + len + BORDER_PIXELS - 1, y + len + BORDER_PIXELS - 1);
+        // all four corners are closed
+        for (px, py) in [(left, top), (right, top), (left, bottom), (right, bottom)].iter() {
+            assert_eq!(pixel(&display, *px, *py), border);
+        }
+        // the frame ends where the board starts
+        assert_eq!(pixel(&display, x - 1, y - 1), border);
+        assert_ne!(pixel(&display, x, y), border);
+        assert_ne!(pixel(&display, left - 1, top - 1), border);
+    }
+
+    #[test]
+    fn test_move_count() {
+        let mut world = World::new(640, 480, 4);
+        world.reset(640, 480);
+        let mut pattern = [[None; 4]; 4];
+        pattern[0][0] = Some(2);
+        world.board.fill_pattern(&pattern);
+
+        // the first move can't change the board
+        for key in [KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowLeft].iter() {
+            world.board.clear_changed();
+            world.on_keypress(DecodedKey::RawKey(*key));
+        }
+        assert_eq!(world.move_count, 2);
+
+        // an undone move doesn't count
+        world.board.clear_changed();
+        world.on_keypress(DecodedKey::Unicode('u'));
+        assert_eq!(world.move_count, 1);
+
+        world.reset(640, 480);
+        assert_eq!(world.move_count, 0);
+    }
+
+    #[test]
+    fn test_slide_animation() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480, 4);
+        world.reset(640, 480);
+        let mut pattern = [[None; 4]; 4];
+        pattern[0][0] = Some(2);
+        pattern[0][1] = Some(2);
+        world.board.fill_pattern(&pattern);
+        world.draw(&mut display);
+
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.animation_ticks, ANIMATION_TICKS);
+        let pos = world.tile_pos(0, 0);
+        let center = (pos.x + world.tile_size / 2, pos.y + world.tile_size / 2);
+
+        // the merged tile flashes white
+        world.draw(&mut display);
+        assert_eq!(pixel(&display, center.0, center.1), (0xff, 0xff, 0xff));
+        assert!(world.board.has_changed());
+
+        // then it grows from the center of its cell
+        world.draw(&mut display);
+        let empty = match EMPTY_TILE_COLOR {
+            Color::RGB32(rgb) => ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8),
+            _ => unreachable!(),
+        };
+        assert_eq!(pixel(&display, pos.x, pos.y), empty);
+        assert_ne!(pixel(&display, center.0, center.1), empty);
+
+        world.draw(&mut display);
+        world.draw(&mut display);
+        assert_eq!(world.animation_ticks, 0);
+        assert_ne!(pixel(&display, pos.x, pos.y), empty);
+    }
+
+    #[test]
+    fn test_large_tile_digits() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480, 4);
+        world.reset(640, 480);
+        let mut pattern = [[None; 4]; 4];
+        pattern[0][0] = Some(2);
+        world.board.fill_pattern(&pattern);
+        world.draw(&mut display);
+
+        // the "2" is 16 pixels tall, its white bottom row ends below the
+        // middle of the tile, where an 8 pixel glyph would already end
+        let pos = world.tile_pos(0, 0);
+        let (x, y) = (
+            pos.x + (world.tile_size - 9) / 2,
+            pos.y + (world.tile_size - 16) / 2,
+        );
+        assert_eq!(pixel(&display, x + 2, y + 13), (255, 255, 255));
+        assert_ne!(pixel(&display, x + 2, y + 14), (255, 255, 255));
+    }
 
-        self.board.clear_changed();
+    #[test]
+    fn test_autoplay() {
+        let mut world = World::new(640, 480, 4);
+        world.reset(640, 480);
+        let mut pattern = [[None; 4]; 4];
+        pattern[0][3] = Some(2);
+        pattern[1][3] = Some(4);
+        world.board.fill_pattern(&pattern);
+        world.board.clear_changed();
+
+        // moving left keeps both tiles closest to the top left corner
+        assert_eq!(autoplay_direction(&world.board), Some(Direction::Left));
+
+        world.on_keypress(DecodedKey::Unicode('a'));
+        assert!(world.autoplay);
+        // the keyboard doesn't move the board while autoplaying
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowRight));
+        assert_eq!(world.move_count, 0);
+
+        assert_eq!(world.step(), GameState::Live);
+        assert_eq!(world.move_count, 1);
+        assert_eq!(world.board.tile_at(0, 0).val, Some(2));
+        assert_eq!(world.board.tile_at(1, 0).val, Some(4));
+        // the next move waits until this one is drawn
+        world.step();
+        assert_eq!(world.move_count, 1);
+
+        world.on_keypress(DecodedKey::Unicode('A'));
+        assert!(!world.autoplay);
     }
 }
@@ -1,10 +1,21 @@
 use lazy_static::lazy_static;
 use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
 use x86_64::structures::tss::TaskStateSegment;
-use x86_64::VirtAddr;
+use x86_64::{PrivilegeLevel, VirtAddr};
 
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
+// The user segments are reserved for future user space support and not used
+// yet. Entries 0 to 4 hold the null, kernel code, tss (two entries) and kernel
+// data descriptors. The user data segment comes before the user code segment,
+// as sysret expects it.
+pub const USER_DATA_SELECTOR: SegmentSelector = SegmentSelector::new(5, PrivilegeLevel::Ring3);
+pub const USER_CODE_SELECTOR: SegmentSelector = SegmentSelector::new(6, PrivilegeLevel::Ring3);
+
+// both user selectors must request privilege level 3
+const _: [(); 1] = [(); (USER_DATA_SELECTOR.0 & 0b11 == 3) as usize];
+const _: [(); 1] = [(); (USER_CODE_SELECTOR.0 & 0b11 == 3) as usize];
+
 lazy_static! {
     static ref TSS: TaskStateSegment = {
         let mut tss = TaskStateSegment::new();
@@ -29,6 +40,11 @@ lazy_static! {
         // needed so that IRET doesn't produce a general protection interrupt
         let data_selector = gdt.add_entry(Descriptor::kernel_data_segment());
 
+        let user_data_selector = gdt.add_entry(Descriptor::user_data_segment());
+        let user_code_selector = gdt.add_entry(Descriptor::user_code_segment());
+        assert!(user_data_selector == USER_DATA_SELECTOR);
+        assert!(user_code_selector == USER_CODE_SELECTOR);
+
         (
             gdt,
             Selectors {
@@ -46,6 +62,16 @@ struct Selectors {
     data_selector: SegmentSelector,
 }
 
+#[allow(dead_code)]
+pub fn user_code_selector() -> SegmentSelector {
+    USER_CODE_SELECTOR
+}
+
+#[allow(dead_code)]
+pub fn user_data_selector() -> SegmentSelector {
+    USER_DATA_SELECTOR
+}
+
 pub fn init() {
     use x86_64::instructions::segmentation::{Segment, CS, SS};
     use x86_64::instructions::tables::load_tss;
@@ -59,3 +85,33 @@ pub fn init() {
         SS::set_reg(GDT.1.data_selector);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x86_64::structures::gdt::DescriptorFlags;
+
+    fn flags(descriptor: Descriptor) -> DescriptorFlags {
+        match descriptor {
+            Descriptor::UserSegment(bits) => DescriptorFlags::from_bits_truncate(bits),
+            Descriptor::SystemSegment(_, _) => panic!("expected a user segment"),
+        }
+    }
+
+    #[test]
+    fn test_user_segments() {
+        let code = flags(Descriptor::user_code_segment());
+        assert!(code.contains(
+            DescriptorFlags::USER_SEGMENT
+                | DescriptorFlags::LONG_MODE
+                | DescriptorFlags::DPL_RING_3
+        ));
+
+        let data = flags(Descriptor::user_data_segment());
+        assert!(data.contains(DescriptorFlags::USER_SEGMENT | DescriptorFlags::DPL_RING_3));
+        assert!(!data.contains(DescriptorFlags::EXECUTABLE));
+
+        assert_eq!(user_code_selector().rpl(), PrivilegeLevel::Ring3);
+        assert_eq!(user_data_selector().rpl(), PrivilegeLevel::Ring3);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{gdt, hlt_loop, serial_print, serial_println};
+use crate::{gdt, hlt_loop, serial_print, serial_println, serial_warn};
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
 use pic8259::ChainedPics;
 use spin;
@@ -12,6 +13,9 @@ pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    // the lowest priority line of each PIC, also raised for spurious irqs
+    Irq7 = PIC_1_OFFSET + 7,
+    Irq15 = PIC_2_OFFSET + 7,
 }
 
 impl InterruptIndex {
@@ -27,6 +31,19 @@ impl InterruptIndex {
 pub static PICS: spin::Mutex<ChainedPics> =
     spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
+const PIC_1_COMMAND: u16 = 0x20;
+const PIC_2_COMMAND: u16 = 0xA0;
+// OCW3 to make the next read of the command port return the in-service register
+const OCW3_READ_ISR: u8 = 0x0B;
+const CMD_END_OF_INTERRUPT: u8 = 0x20;
+
+static SPURIOUS_IRQS: AtomicU64 = AtomicU64::new(0);
+
+#[allow(dead_code)]
+pub fn spurious_irq_count() -> u64 {
+    SPURIOUS_IRQS.load(Ordering::Relaxed)
+}
+
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
@@ -39,6 +56,8 @@ lazy_static! {
         }
         idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
         idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Irq7.as_usize()].set_handler_fn(irq7_handler);
+        idt[InterruptIndex::Irq15.as_usize()].set_handler_fn(irq15_handler);
         idt
     };
 }
@@ -93,3 +112,82 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
             .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
     }
 }
+
+// is_spurious returns whether an irq on line 7 of a PIC is spurious, which is
+// the case if the PIC doesn't have the line in service
+fn is_spurious(isr: u8) -> bool {
+    isr & (1 << 7) == 0
+}
+
+// read_isr reads the in-service register of the PIC with the given command port
+fn read_isr(command_port: u16) -> u8 {
+    use x86_64::instructions::port::Port;
+    let mut port = Port::new(command_port);
+
+    unsafe {
+        port.write(OCW3_READ_ISR);
+        port.read()
+    }
+}
+
+// count_spurious_irq counts a spurious irq and returns whether it was the
+// first one
+fn count_spurious_irq() -> bool {
+    SPURIOUS_IRQS.fetch_add(1, Ordering::Relaxed) == 0
+}
+
+fn spurious_irq_handler(irq: u8) {
+    if count_spurious_irq() {
+        serial_warn!("spurious IRQ{}", irq);
+    }
+}
+
+extern "x86-interrupt" fn irq7_handler(_stack_frame: InterruptStackFrame) {
+    if is_spurious(read_isr(PIC_1_COMMAND)) {
+        // no end of interrupt, the master never had the irq in service
+        spurious_irq_handler(7);
+        return;
+    }
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Irq7.as_u8());
+    }
+}
+
+extern "x86-interrupt" fn irq15_handler(_stack_frame: InterruptStackFrame) {
+    use x86_64::instructions::port::Port;
+
+    if is_spurious(read_isr(PIC_2_COMMAND)) {
+        // the master forwarded the irq from the slave, so only it needs an
+        // end of interrupt
+        spurious_irq_handler(15);
+        let mut port = Port::new(PIC_1_COMMAND);
+        unsafe { port.write(CMD_END_OF_INTERRUPT) };
+        return;
+    }
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Irq15.as_u8());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_spurious() {
+        assert!(is_spurious(0));
+        assert!(is_spurious(0b0111_1111));
+        assert!(!is_spurious(0b1000_0000));
+        assert!(!is_spurious(0xff));
+    }
+
+    #[test]
+    fn test_count_spurious_irq() {
+        let before = spurious_irq_count();
+        count_spurious_irq();
+        count_spurious_irq();
+        assert_eq!(spurious_irq_count(), before + 2);
+    }
+}
@@ -7,6 +7,7 @@ extern crate alloc;
 
 use alloc::boxed::Box;
 use alloc::sync::Arc;
+use bootloader::boot_info::MemoryRegionKind;
 use bootloader::{entry_point, BootInfo};
 use core::panic::PanicInfo;
 use futures_util::stream::StreamExt;
@@ -16,6 +17,7 @@ use task::keyboard::ScancodeStream;
 use task::tick::TickStream;
 
 mod allocator;
+mod collections;
 mod display;
 mod game2048;
 mod gdt;
@@ -35,21 +37,68 @@ lazy_static! {
 
 fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     serial_println!("init system...");
+    if let Err(err) = memory::validate_boot_info(boot_info) {
+        serial_error!("{}", err);
+        hlt_loop();
+    }
     gdt::init();
     interrupts::init_idt();
     unsafe { interrupts::PICS.lock().initialize() };
+    // validate_boot_info made sure the offset is there
     let phys_mem_offset =
         x86_64::VirtAddr::new(boot_info.physical_memory_offset.into_option().unwrap());
     let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    serial_assert!(
+        boot_info
+            .memory_regions
+            .iter()
+            .any(|r| r.kind == MemoryRegionKind::Usable),
+        "the memory map has no usable region"
+    );
+    serial_println!("{} memory regions", boot_info.memory_regions.len());
+    for region in boot_info.memory_regions.iter() {
+        serial_debug_if!(
+            2,
+            region.kind != MemoryRegionKind::Usable,
+            "unusable memory {:#x}..{:#x}: {:?}",
+            region.start,
+            region.end,
+            region.kind
+        );
+    }
     let mut frame_allocator =
         unsafe { memory::BootInfoFrameAllocator::init(&boot_info.memory_regions) };
-    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    // the display's back buffer and draw buffer are each as large as the
+    // framebuffer
+    let framebuffer_len = boot_info
+        .framebuffer
+        .as_ref()
+        .map_or(0, |framebuffer| framebuffer.info().byte_len);
+    let heap_size = allocator::HEAP_SIZE + 2 * framebuffer_len;
+    match allocator::init_heap(&mut mapper, &mut frame_allocator, heap_size) {
+        Ok(size) => serial_println!("heap: {} KiB", size / 1024),
+        Err(err) => {
+            serial_error!("heap initialization failed: {}", err);
+            hlt_loop();
+        }
+    }
 
     serial_println!("init done!");
 
     if let Some(framebuffer) = boot_info.framebuffer.as_mut() {
-        DISPLAY.lock().set_framebuffer(framebuffer);
+        if let Err(err) = DISPLAY.lock().set_framebuffer(framebuffer) {
+            serial_error!("unsupported framebuffer: {:?}", err);
+            hlt_loop();
+        }
         DISPLAY.lock().clear();
+        if !DISPLAY.lock().enable_back_buffer() {
+            serial_warn!("no memory for a display back buffer");
+        }
+        if !DISPLAY.lock().enable_double_buffering() {
+            serial_warn!("no memory for double buffering");
+        }
+        DISPLAY.lock().set_fps_target(FPS_TARGET);
+
         // welcome();
         // DISPLAY.lock().clear();
         // DISPLAY.lock().draw_borders();
@@ -66,11 +115,28 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
             )
         };
         serial_println!("width: {}, height: {}", width, height);
-        let game_snake = Box::new(snake::world::World::new(width, height));
-        let game_2048 = Box::new(game2048::World::new(width, height));
+        let game_snake = Box::new(snake::world::World::new(width, height).with_food_count(3));
+        let game_snake_2p = Box::new(
+            snake::world::World::new(width, height)
+                .with_food_count(3)
+                .with_two_players(),
+        );
+        let game_snake_wrap = Box::new(
+            snake::world::World::new(width, height)
+                .with_food_count(3)
+                .with_collision_mode(snake::world::CollisionMode::Wrap),
+        );
+        let game_2048 = Box::new(game2048::World::new(width, height, 4));
+        let game_2048_small = Box::new(game2048::World::new(width, height, 3));
+        let game_2048_large = Box::new(game2048::World::new(width, height, 6));
         let mut world = Arc::new(spin::Mutex::new(world::World::new(width, height)));
         world.lock().add_game(game_snake, "snake");
-        world.lock().add_game(game_2048, "2048");
+        world.lock().add_game(game_snake_2p, "snake (2P)");
+        world.lock().add_game(game_snake_wrap, "snake (wrap)");
+        world.lock().add_game(game_2048_small, "2048 (3x3)");
+        world.lock().add_game(game_2048, "2048 (4x4)");
+        world.lock().add_game(game_2048_large, "2048 (6x6)");
+        world.lock().apply_config_string(CONFIG_STR);
         serial_println!("enable interrupts");
         x86_64::instructions::interrupts::enable();
 
@@ -91,9 +157,12 @@ pub fn hlt_loop() -> ! {
     }
 }
 
+// panic halts the whole system, even when only one task panicked. Without
+// unwinding there is no way to leave the panicked poll: its stack frame and the
+// spin locks it holds would stay behind, so resuming the executor isn't safe.
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    serial_println!("{}", info);
+    serial_error!("{}", info);
     hlt_loop();
 }
 
@@ -102,21 +171,57 @@ fn alloc_error_handler(layout: alloc::alloc::Layout) -> ! {
     panic!("allocation error: {:?}", layout)
 }
 
+// CONFIG_STR stands in for a boot command line, see
+// World::apply_config_string
+const CONFIG_STR: &str = "";
+
+// FPS_TARGET is how often the running game is drawn per second. The games
+// still step on every tick and catch up on the next drawn frame.
+const FPS_TARGET: u32 = 9;
+
+// FADE_IN_TICKS is the duration of the fade in after boot, one second
+const FADE_IN_TICKS: u64 = 18;
+
 async fn handle_ticks(world: Arc<spin::Mutex<world::World>>) {
     serial_println!("handle_ticks");
-    let mut stream = TickStream::new();
+    let mut stream = match TickStream::new_or_get() {
+        Ok(stream) => stream,
+        Err(err) => {
+            serial_error!("handle_ticks: {:?}", err);
+            return;
+        }
+    };
     serial_println!("handle_ticks: new()");
+    let mut was_paused = false;
     while let Some(_) = stream.next().await {
         // continue;
         // serial_println!("handle_ticks: one tick()");
         let mut world = world.lock();
+        // drop the ticks queued up while paused to avoid a burst of steps
+        let paused = world.is_paused();
+        if was_paused && !paused {
+            stream.drain();
+        }
+        was_paused = paused;
         // serial_println!("handle_ticks: one tick() done");
-        world.on_tick(&mut DISPLAY.lock());
+        let mut display = DISPLAY.lock();
+        display.tick();
+        world.on_tick(&mut display);
+        // fade in the welcome screen, drawn on the first tick
+        let tick = display.current_tick();
+        display.animate_fade_in(tick, 1, FADE_IN_TICKS);
+        display.flip();
     }
 }
 
 async fn handle_keypresses(world: Arc<spin::Mutex<world::World>>) {
-    let mut scancodes = ScancodeStream::new();
+    let mut scancodes = match ScancodeStream::new_or_get() {
+        Ok(stream) => stream,
+        Err(err) => {
+            serial_error!("handle_keypresses: {:?}", err);
+            return;
+        }
+    };
     let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
 
     while let Some(scancode) = scancodes.next().await {
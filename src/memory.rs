@@ -1,9 +1,51 @@
 use bootloader::boot_info::{MemoryRegions, MemoryRegionKind};
+use bootloader::BootInfo;
+use core::fmt;
 use x86_64::{
     structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB},
     PhysAddr, VirtAddr,
 };
 
+/// Reasons why the boot info can't be used to set up virtual memory.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BootInfoError {
+    /// The bootloader didn't map the physical memory.
+    MissingPhysicalMemoryOffset,
+    /// The physical memory is mapped at an offset that isn't page aligned.
+    MisalignedPhysicalMemoryOffset(u64),
+}
+
+impl fmt::Display for BootInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BootInfoError::MissingPhysicalMemoryOffset => write!(
+                f,
+                "No physical memory offset provided, cannot initialize virtual memory"
+            ),
+            BootInfoError::MisalignedPhysicalMemoryOffset(offset) => write!(
+                f,
+                "Physical memory offset {:#x} is not page aligned",
+                offset
+            ),
+        }
+    }
+}
+
+/// Checks that the boot info provides what `init` needs.
+pub fn validate_boot_info(boot_info: &BootInfo) -> Result<(), BootInfoError> {
+    validate_physical_memory_offset(boot_info.physical_memory_offset.into_option()).map(|_| ())
+}
+
+fn validate_physical_memory_offset(offset: Option<u64>) -> Result<u64, BootInfoError> {
+    match offset {
+        None => Err(BootInfoError::MissingPhysicalMemoryOffset),
+        Some(offset) if offset % 4096 != 0 => {
+            Err(BootInfoError::MisalignedPhysicalMemoryOffset(offset))
+        }
+        Some(offset) => Ok(offset),
+    }
+}
+
 /// Initialize a new OffsetPageTable.
 ///
 /// This function is unsafe because the caller must guarantee that the
@@ -81,4 +123,25 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
         self.next += 1;
         frame
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_physical_memory_offset() {
+        assert_eq!(
+            validate_physical_memory_offset(None),
+            Err(BootInfoError::MissingPhysicalMemoryOffset)
+        );
+        assert_eq!(
+            validate_physical_memory_offset(Some(0x1234)),
+            Err(BootInfoError::MisalignedPhysicalMemoryOffset(0x1234))
+        );
+        assert_eq!(
+            validate_physical_memory_offset(Some(0x0000_4000_0000_0000)),
+            Ok(0x0000_4000_0000_0000)
+        );
+    }
+}
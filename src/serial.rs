@@ -2,6 +2,9 @@ use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
 
+// DEBUG_LEVEL is the highest level serial_debug_if! prints
+pub const DEBUG_LEVEL: u8 = 1;
+
 lazy_static! {
     pub static ref SERIAL1: Mutex<SerialPort> = {
         let mut serial_port = unsafe { SerialPort::new(0x3F8) };
@@ -39,3 +42,89 @@ macro_rules! serial_println {
     ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(
         concat!($fmt, "\n"), $($arg)*));
 }
+
+/// Prints an error message to the host through the serial interface.
+#[macro_export]
+macro_rules! serial_error {
+    ($($arg:tt)*) => ($crate::serial_println!("ERROR: {}", format_args!($($arg)*)));
+}
+
+/// Prints an informational message to the host through the serial interface.
+#[macro_export]
+macro_rules! serial_info {
+    ($($arg:tt)*) => ($crate::serial_println!("INFO: {}", format_args!($($arg)*)));
+}
+
+/// Prints a warning to the host through the serial interface.
+#[macro_export]
+macro_rules! serial_warn {
+    ($($arg:tt)*) => ($crate::serial_println!("WARNING: {}", format_args!($($arg)*)));
+}
+
+/// Logs the message and the location via serial and panics if the condition
+/// is false.
+#[macro_export]
+macro_rules! serial_assert {
+    ($cond:expr, $($arg:tt)*) => {
+        if !$cond {
+            $crate::serial_error!(
+                "ASSERT FAILED at {}:{}: {}",
+                file!(),
+                line!(),
+                format_args!($($arg)*)
+            );
+            panic!("assertion failed: {}", stringify!($cond));
+        }
+    };
+}
+
+/// Like serial_assert!, for two values that must be equal.
+#[macro_export]
+macro_rules! serial_assert_eq {
+    ($left:expr, $right:expr, $($arg:tt)*) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::serial_error!(
+                        "ASSERT FAILED at {}:{}: {:?} != {:?}: {}",
+                        file!(),
+                        line!(),
+                        left,
+                        right,
+                        format_args!($($arg)*)
+                    );
+                    panic!("assertion failed: {} == {}", stringify!($left), stringify!($right));
+                }
+            }
+        }
+    };
+}
+
+/// Prints a debug message if the condition holds and level is at most
+/// DEBUG_LEVEL.
+#[macro_export]
+macro_rules! serial_debug_if {
+    ($level:expr, $cond:expr, $($arg:tt)*) => {
+        if $level <= $crate::serial::DEBUG_LEVEL && $cond {
+            $crate::serial_println!("DEBUG: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DEBUG_LEVEL;
+
+    #[test]
+    fn test_serial_assert_passes() {
+        serial_assert!(1 + 1 == 2, "math is broken");
+        serial_assert_eq!(2 * 2, 4, "math is broken");
+        serial_debug_if!(DEBUG_LEVEL + 1, true, "not printed");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_serial_assert_fails() {
+        serial_assert!(1 + 1 == 3, "expected");
+    }
+}
@@ -1 +1 @@
-pub mod world;
\ No newline at end of file
+pub mod world;
@@ -1,27 +1,197 @@
+use crate::collections::RingBuffer;
 use crate::display::{Color, Display, BLOCK_SIZE};
-use crate::serial_println;
-use crate::world::{Game, GameState, Direction};
-use alloc::collections::VecDeque;
+use crate::world::{transition, CharsBuf, Direction, Game, GameState};
+use crate::{serial_print, serial_println};
+use alloc::vec::Vec;
+use core::fmt::Write;
 use rand::prelude::*;
 
-
 use crate::world::ScreenPos as Point;
 
+// BORDER_COLOR is the color of the walls of the arena
+pub const BORDER_COLOR: Color = Color::DarkGreen;
+
+// WRAP_BORDER_COLOR is the dimmed border color in CollisionMode::Wrap, where
+// the border can be passed
+const WRAP_BORDER_COLOR: Color = Color::Grey;
+
+// SCORE_GOAL is the score at which the progress bar is full
+const SCORE_GOAL: usize = 50;
+
+// HEAD_UP, HEAD_RIGHT, HEAD_DOWN and HEAD_LEFT are the 8x8 sprites of the
+// snake head moving in each direction, for Display::draw_bitmap
+const HEAD_UP: &[u8] = &[
+    0b00111100, 0b01111110, 0b11011011, 0b11111111, 0b11111111, 0b11111111, 0b11111111, 0b11111111,
+];
+const HEAD_RIGHT: &[u8] = &[
+    0b11111100, 0b11111110, 0b11111011, 0b11111111, 0b11111111, 0b11111011, 0b11111110, 0b11111100,
+];
+const HEAD_DOWN: &[u8] = &[
+    0b11111111, 0b11111111, 0b11111111, 0b11111111, 0b11111111, 0b11011011, 0b01111110, 0b00111100,
+];
+const HEAD_LEFT: &[u8] = &[
+    0b00111111, 0b01111111, 0b11011111, 0b11111111, 0b11111111, 0b11011111, 0b01111111, 0b00111111,
+];
+
+// APPLE is the 8x8 sprite of the food, drawn in the color of its FoodKind
+const APPLE: &[u8] = &[
+    0b00001000, 0b01110110, 0b11111111, 0b11111111, 0b11111111, 0b11111111, 0b01111110, 0b00111100,
+];
+
+// head_sprite returns the head sprite for a snake moving in direction
+fn head_sprite(direction: Direction) -> &'static [u8] {
+    match direction {
+        Direction::Up => HEAD_UP,
+        Direction::Right => HEAD_RIGHT,
+        Direction::Down => HEAD_DOWN,
+        Direction::Left => HEAD_LEFT,
+    }
+}
+
+// DEFAULT_MAX_SPEED is the speed the snake accelerates to, moving on every
+// tick
+const DEFAULT_MAX_SPEED: usize = 10;
+
+// LEVEL_FOOD is the number of food items per level. Each new level speeds
+// the snake up and adds an obstacle.
+const LEVEL_FOOD: usize = 5;
+
+// BANNER_TICKS is how long the level banner stays on the arena
+const BANNER_TICKS: u64 = 30;
+
+// MAX_SNAKE_LENGTH is the number of body segments a snake can have
+const MAX_SNAKE_LENGTH: usize = 1024;
+
+// SnakeBody holds the body segments of a snake, from the tail to the block
+// behind the head
+type SnakeBody = RingBuffer<Point, MAX_SNAKE_LENGTH>;
+
+// DEFAULT_FOOD_COUNT is the number of food items on the arena at once
+const DEFAULT_FOOD_COUNT: usize = 1;
+
+// PAUSE_SHADE is the translucent panel behind the pause message
+const PAUSE_SHADE: Color = Color::RGBA32(0x80000000);
+
+// FoodKind decides what eating a food item does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoodKind {
+    // grows the snake by one segment and scores a point
+    Regular,
+    // shrinks the snake by two segments
+    Poison,
+}
+
+// BONUS_POINTS is the score of the bonus food, see World::bonus_food
+const BONUS_POINTS: usize = 3;
+
+// BONUS_TICKS is how long bonus food stays on the arena
+const BONUS_TICKS: u64 = 50;
+
+impl FoodKind {
+    fn color(self) -> Color {
+        match self {
+            FoodKind::Regular => Color::LightRed,
+            FoodKind::Poison => Color::Magenta,
+        }
+    }
+}
+
+// PLACEMENT_ATTEMPTS is how many random blocks World::random_free_point
+// tries before it gives up on a crowded arena
+const PLACEMENT_ATTEMPTS: usize = 1000;
+
+// OBSTACLE_CLEARANCE is the number of blocks in front of each head that new
+// obstacles stay out of, so a level up never puts one right in the way
+const OBSTACLE_CLEARANCE: usize = 3;
+
+// REPLAY_SPEED is the number of replayed steps per tick in SnakeMode::Playback
+const REPLAY_SPEED: usize = 2;
+
+// SnakeMode decides where a World takes its directions from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnakeMode {
+    // steered by the keyboard
+    Live,
+    // steered by the keyboard, recording the direction changes for a replay
+    Recording,
+    // showing the replay of the recorded game, the keyboard is ignored
+    Playback,
+}
+
+// CollisionMode decides what happens when the snake hits the border
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionMode {
+    WallDeath,
+    Wrap,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct World {
     pub width: usize,
     pub height: usize,
-    pub food: Option<Point>,
+    pub food: Vec<(Point, FoodKind)>,
+    // the top left block of the 2x2 blocks bonus food and the tick it
+    // disappears on
+    pub bonus_food: Option<(Point, u64)>,
+    pub max_food: usize,
+    // blocks the snake dies on, one more per level
+    pub obstacles: Vec<Point>,
+    // level starts at 1 and goes up every LEVEL_FOOD points
+    pub level: usize,
+    // the level banner is shown while this counts down to 0
+    pub banner_ticks_remaining: u64,
+    // the banner is on screen and has to be cleared when it runs out
+    banner_drawn: bool,
     pub score: usize,
     pub game_over: bool,
+    // the snake moves on speed out of max_speed ticks
     pub speed: usize,
+    pub max_speed: usize,
+    // direction is the direction of the next step, last_direction the one
+    // of the last step taken
     pub direction: Direction,
+    last_direction: Direction,
     pub snake_length: usize,
     pub snake_head: Point,
-    pub snake_body: VecDeque<Point>,
-    pub snake_tail: Option<Point>,
+    pub snake_body: SnakeBody,
+    // the blocks to clear on the next draw: segments left behind and the
+    // blocks of eaten or expired bonus food
+    pub snake_tail: Vec<Point>,
+    pub collision_mode: CollisionMode,
+    // paused is toggled with space, the snake doesn't move while it is set
+    pub paused: bool,
+    // the frame below the pause message, drawn back on resume
+    pause_snapshot: Option<Vec<u8>>,
+    pub mode: SnakeMode,
+    // the tick and new direction of every turn of the first snake, in
+    // SnakeMode::Recording
+    pub recording: Vec<(u64, Direction)>,
+    // the rng as it was on reset, so the replay places the same food
+    start_rng: rand::rngs::SmallRng,
+    // the world replaying the recording and the next turn to replay
+    replay: Option<alloc::boxed::Box<World>>,
+    replay_pos: usize,
+    // two_player adds a second snake steered with WASD, the first one
+    // follows the arrow keys only
+    pub two_player: bool,
+    pub snake2_head: Point,
+    pub snake2_body: SnakeBody,
+    pub snake2_direction: Direction,
+    snake2_last_direction: Direction,
+    pub snake2_length: usize,
+    pub snake2_score: usize,
+    // the game is over once all snakes are dead
+    snake_alive: bool,
+    snake2_alive: bool,
+    transition_ticks: Option<u8>,
+    borders_drawn: bool,
+    // the score line needs to be redrawn
+    score_dirty: bool,
     rng: rand::rngs::SmallRng,
+    // counter collects the speed of the snake, see step
     counter: u64,
+    // tick counts the calls to step, for the lifetime of bonus food
+    tick: u64,
 }
 
 impl World {
@@ -33,37 +203,510 @@ impl World {
         World {
             width,
             height,
-            food: None,
+            food: Vec::new(),
+            bonus_food: None,
+            max_food: DEFAULT_FOOD_COUNT,
+            obstacles: Vec::new(),
+            level: 1,
+            banner_ticks_remaining: 0,
+            banner_drawn: false,
             score: 0,
             game_over: false,
-            speed: 1,
+            speed: DEFAULT_MAX_SPEED / 2,
+            max_speed: DEFAULT_MAX_SPEED,
             direction: Direction::Right,
+            last_direction: Direction::Right,
             snake_length: 1,
             snake_head: start,
-            snake_body: VecDeque::new(),
-            snake_tail: None,
+            snake_body: SnakeBody::new(),
+            snake_tail: Vec::new(),
+            collision_mode: CollisionMode::WallDeath,
+            paused: false,
+            pause_snapshot: None,
+            mode: SnakeMode::Recording,
+            recording: Vec::new(),
+            start_rng: rand::rngs::SmallRng::from_seed([0; 32]),
+            replay: None,
+            replay_pos: 0,
+            two_player: false,
+            snake2_head: snake2_start(width, height),
+            snake2_body: SnakeBody::new(),
+            snake2_direction: Direction::Left,
+            snake2_last_direction: Direction::Left,
+            snake2_length: 1,
+            snake2_score: 0,
+            snake_alive: true,
+            snake2_alive: true,
+            transition_ticks: None,
+            borders_drawn: false,
+            score_dirty: true,
             rng: rand::rngs::SmallRng::from_seed([0; 32]),
             counter: 0,
+            tick: 0,
         }
     }
 
-    fn place_random_food(&mut self) {
-        let mut point = Point { x: 0, y: 0 };
-        while self.snake_body.contains(&point)
-            || self.snake_head == point
-            || point.x >= self.width - 2 * BLOCK_SIZE
-            || point.y >= self.height - 2 * BLOCK_SIZE
-            || point.x <= 2 * BLOCK_SIZE
-            || point.y <= 2 * BLOCK_SIZE
-        {
-            point = Point {
+    // grow adds a segment to the snake and points to the score, going up a
+    // level every LEVEL_FOOD points
+    fn grow(&mut self, points: usize) {
+        self.score += points;
+        self.score_dirty = true;
+        // with two snakes the better one sets the level
+        let level = 1 + self.score.max(self.snake2_score) / LEVEL_FOOD;
+        while self.level < level {
+            self.level_up();
+        }
+        // the body can't hold more segments, the snake just stops growing
+        self.snake_length = (self.snake_length + 1).min(MAX_SNAKE_LENGTH);
+    }
+
+    // level_up speeds the snake up, adds an obstacle and shows the banner
+    fn level_up(&mut self) {
+        self.level += 1;
+        self.speed = (self.speed + 1).min(self.max_speed);
+        if let Some(point) = self.random_obstacle_point() {
+            self.obstacles.push(point);
+        }
+        self.banner_ticks_remaining = BANNER_TICKS;
+    }
+
+    // with_two_players adds the second snake
+    pub fn with_two_players(mut self) -> Self {
+        self.two_player = true;
+        self
+    }
+
+    // swap_snakes exchanges the state of the two snakes, so the code written
+    // for the first one can move and draw the second one
+    fn swap_snakes(&mut self) {
+        core::mem::swap(&mut self.snake_head, &mut self.snake2_head);
+        core::mem::swap(&mut self.snake_body, &mut self.snake2_body);
+        core::mem::swap(&mut self.direction, &mut self.snake2_direction);
+        core::mem::swap(&mut self.last_direction, &mut self.snake2_last_direction);
+        core::mem::swap(&mut self.snake_length, &mut self.snake2_length);
+        core::mem::swap(&mut self.score, &mut self.snake2_score);
+        core::mem::swap(&mut self.snake_alive, &mut self.snake2_alive);
+    }
+
+    // is_snake2 reports whether the block at p is part of the second snake
+    fn is_snake2(&self, p: Point) -> bool {
+        self.two_player && (self.snake2_head == p || self.snake2_body.contains(&p))
+    }
+
+    // with_collision_mode sets what happens when a snake hits the border
+    pub fn with_collision_mode(mut self, mode: CollisionMode) -> Self {
+        self.collision_mode = mode;
+        self
+    }
+
+    // border_color returns the color of the arena walls
+    fn border_color(&self) -> Color {
+        match self.collision_mode {
+            CollisionMode::WallDeath => BORDER_COLOR,
+            CollisionMode::Wrap => WRAP_BORDER_COLOR,
+        }
+    }
+
+    // with_food_count makes the arena hold n food items at once
+    pub fn with_food_count(mut self, n: usize) -> Self {
+        self.max_food = n;
+        self
+    }
+
+    // fill_food places food until there are max_food items
+    fn fill_food(&mut self) {
+        while self.food.len() < self.max_food {
+            // a full arena has no room for more
+            if !self.place_random_food() {
+                break;
+            }
+        }
+    }
+
+    // place_random_food adds a food item, one in five is poison. Every tenth
+    // time bonus food shows up as well, if there is none yet. It returns
+    // false if there was no room for the food.
+    fn place_random_food(&mut self) -> bool {
+        let point = match self.random_free_point(1) {
+            Some(point) => point,
+            None => return false,
+        };
+        let kind = match self.rand(5) {
+            0 => FoodKind::Poison,
+            _ => FoodKind::Regular,
+        };
+        self.food.push((point, kind));
+        if self.bonus_food.is_none() && self.rand(10) == 0 {
+            if let Some(point) = self.random_free_point(2) {
+                self.bonus_food = Some((point, self.tick + BONUS_TICKS));
+            }
+        }
+        true
+    }
+
+    // random_free_point returns the top left corner of a random square of
+    // size x size empty blocks inside the arena, or None if it didn't find
+    // one in PLACEMENT_ATTEMPTS tries
+    fn random_free_point(&mut self, size: usize) -> Option<Point> {
+        self.random_point_where(size, Self::is_free)
+    }
+
+    // random_obstacle_point returns a random free block that isn't in front
+    // of either snake's head
+    fn random_obstacle_point(&mut self) -> Option<Point> {
+        self.random_point_where(1, |world, p| world.is_free(p) && !world.is_in_front(p))
+    }
+
+    // random_point_where returns the top left corner of a random square of
+    // size x size blocks that all satisfy free
+    fn random_point_where(
+        &mut self,
+        size: usize,
+        free: impl Fn(&Self, Point) -> bool,
+    ) -> Option<Point> {
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let mut point = Point {
                 x: self.rand(self.width),
                 y: self.rand(self.height),
             };
             point.x = point.x - point.x % BLOCK_SIZE;
             point.y = point.y - point.y % BLOCK_SIZE;
+            let square_free = (0..size).all(|dy| {
+                (0..size).all(|dx| free(self, point.right(dx * BLOCK_SIZE).down(dy * BLOCK_SIZE)))
+            });
+            if square_free {
+                return Some(point);
+            }
+        }
+        None
+    }
+
+    // is_free reports whether the block at p is inside the arena and neither
+    // snake, food nor obstacle
+    fn is_free(&self, p: Point) -> bool {
+        !(self.snake_body.contains(&p)
+            || self.snake_head == p
+            || self.is_snake2(p)
+            || self.obstacles.contains(&p)
+            || self.food.iter().any(|(food, _)| *food == p)
+            || self.is_bonus_food(p)
+            || p.x >= self.width - 2 * BLOCK_SIZE
+            || p.y >= self.height - 2 * BLOCK_SIZE
+            || p.x <= 2 * BLOCK_SIZE
+            || p.y <= 2 * BLOCK_SIZE)
+    }
+
+    // is_in_front reports whether p is within OBSTACLE_CLEARANCE blocks ahead
+    // of a snake's head
+    fn is_in_front(&self, p: Point) -> bool {
+        is_ahead(self.snake_head, self.direction, p)
+            || (self.two_player && is_ahead(self.snake2_head, self.snake2_direction, p))
+    }
+
+    // is_bonus_food reports whether the block at p is part of the bonus food
+    fn is_bonus_food(&self, p: Point) -> bool {
+        match self.bonus_food {
+            Some((bonus, _)) => {
+                p.x >= bonus.x
+                    && p.x < bonus.x + 2 * BLOCK_SIZE
+                    && p.y >= bonus.y
+                    && p.y < bonus.y + 2 * BLOCK_SIZE
+            }
+            None => false,
+        }
+    }
+
+    // remove_bonus_food takes the bonus food off the arena
+    fn remove_bonus_food(&mut self) {
+        if let Some((bonus, _)) = self.bonus_food.take() {
+            self.snake_tail.extend(block_square(bonus, 2));
+        }
+    }
+
+    // eat applies the effect of the food of the given kind
+    fn eat(&mut self, kind: FoodKind) {
+        match kind {
+            FoodKind::Regular => self.grow(1),
+            FoodKind::Poison => self.snake_length = self.snake_length.saturating_sub(2).max(1),
+        }
+    }
+
+    // set_direction_if_valid changes the direction unless the snake would
+    // turn back into itself. It checks against the last step taken, so two
+    // turns between steps can't reverse the snake either.
+    pub fn set_direction_if_valid(&mut self, dir: Direction) {
+        if dir != self.last_direction.opposite() {
+            self.direction = dir;
+        }
+    }
+
+    pub fn queued_direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn grid_width(&self) -> usize {
+        self.width / BLOCK_SIZE
+    }
+
+    pub fn grid_height(&self) -> usize {
+        self.height / BLOCK_SIZE
+    }
+
+    // grid_row renders a row of the world as text with one character per
+    // block: H (head), B (body), h and b for the second snake, F (food), X
+    // (obstacle), # (wall) and . (empty)
+    pub fn grid_row(&self, row: usize) -> impl Iterator<Item = char> + '_ {
+        (0..self.grid_width()).map(move |col| {
+            let p = Point {
+                x: col * BLOCK_SIZE,
+                y: row * BLOCK_SIZE,
+            };
+            if p == self.snake_head {
+                'H'
+            } else if self.snake_body.contains(&p) {
+                'B'
+            } else if self.two_player && p == self.snake2_head {
+                'h'
+            } else if self.is_snake2(p) {
+                'b'
+            } else if self.food.iter().any(|(food, _)| *food == p) || self.is_bonus_food(p) {
+                'F'
+            } else if self.obstacles.contains(&p) {
+                'X'
+            } else if self.is_out_of_bounds(p) {
+                '#'
+            } else {
+                '.'
+            }
+        })
+    }
+
+    // to_grid_string returns all rows of grid_row, each ended by a newline
+    #[cfg(test)]
+    fn to_grid_string(&self) -> Vec<char> {
+        let mut grid = Vec::new();
+        for row in 0..self.grid_height() {
+            grid.extend(self.grid_row(row));
+            grid.push('\n');
+        }
+        grid
+    }
+
+    // is_out_of_bounds reports whether p lies on or outside of the border
+    fn is_out_of_bounds(&self, p: Point) -> bool {
+        p.x >= self.width - 2 * BLOCK_SIZE
+            || p.y >= self.height - 2 * BLOCK_SIZE
+            || p.x <= BLOCK_SIZE
+            || p.y <= BLOCK_SIZE
+    }
+
+    // arena returns the (x, y, w, h) rectangle inside the border
+    fn arena(&self) -> (usize, usize, usize, usize) {
+        (
+            2 * BLOCK_SIZE,
+            2 * BLOCK_SIZE,
+            self.width - 4 * BLOCK_SIZE,
+            self.height - 4 * BLOCK_SIZE,
+        )
+    }
+
+    // progress_bar_rect returns the (x, y, w, h) rectangle of the progress
+    // bar, at the right end of the score line above the top border
+    fn progress_bar_rect(&self) -> (usize, usize, usize, usize) {
+        let w = 12 * BLOCK_SIZE;
+        (self.width - 2 * BLOCK_SIZE - w, 1, w, BLOCK_SIZE - 2)
+    }
+
+    // draw_score writes the score above the top border and shows the
+    // progress towards SCORE_GOAL next to it. Both are outside of the arena,
+    // so the snake never draws over them between two score changes.
+    fn draw_score(&self, display: &mut Display) {
+        let mut line = CharsBuf::<32>::new();
+        if self.two_player {
+            let (p1, p2) = (self.score, self.snake2_score);
+            write!(line, "Lvl:{} P1:{} P2:{}", self.level, p1, p2).unwrap();
+        } else {
+            write!(line, "Lvl:{} Score:{}", self.level, self.score).unwrap();
+        }
+        display.clear_rect(2 * BLOCK_SIZE, 0, self.width - 4 * BLOCK_SIZE, BLOCK_SIZE);
+        display.set_xy(2 * BLOCK_SIZE, 0);
+        let mut prev = ' ';
+        for c in line.chars() {
+            // the numbers are bold, the player numbers aren't
+            display.set_bold(c.is_ascii_digit() && prev != 'P');
+            display.write_char_colored(*c, Color::White, Color::Black);
+            prev = *c;
+        }
+        display.set_bold(false);
+
+        display.draw_progress_bar(
+            self.progress_bar_rect(),
+            (self.score.max(self.snake2_score).min(SCORE_GOAL) * 100 / SCORE_GOAL) as u8,
+            Color::Green,
+            Color::DarkGreen,
+        );
+    }
+
+    // banner_region returns the area covered by the level banner, centered
+    // on the arena
+    fn banner_region(&self) -> (usize, usize, usize, usize) {
+        let (w, h) = (20 * BLOCK_SIZE, 2 * BLOCK_SIZE);
+        ((self.width - w) / 2, (self.height - h) / 2, w, h)
+    }
+
+    // draw_banner writes "Level N!" over the arena. It is drawn after
+    // everything else on every frame, so the snake passes below it.
+    fn draw_banner(&mut self, display: &mut Display) {
+        let (x, y, w, h) = self.banner_region();
+        let mut text = CharsBuf::<16>::new();
+        write!(text, "Level {}!", self.level).unwrap();
+        display.set_font_scale(2);
+        let (tw, th) = display.measure_chars(text.chars());
+        let (tx, ty) = (x + w.saturating_sub(tw) / 2, y + h.saturating_sub(th) / 2);
+        let bg = display.background_color;
+        display.draw_text_at(tx, ty, text.chars(), Color::Yellow, bg);
+        display.set_font_scale(1);
+        self.banner_drawn = true;
+    }
+
+    // pause_region returns the area covered by the pause message, centered
+    // on the arena
+    fn pause_region(&self) -> (usize, usize, usize, usize) {
+        let (w, h) = (14 * BLOCK_SIZE, 4 * BLOCK_SIZE);
+        ((self.width - w) / 2, (self.height - h) / 2, w, h)
+    }
+
+    // draw_paused saves the frame below the pause message, so it can be
+    // restored on resume, and draws the message on a translucent panel
+    fn draw_paused(&mut self, display: &mut Display) {
+        let (x, y, w, h) = self.pause_region();
+        self.pause_snapshot = Some(display.snapshot_region(x, y, w, h));
+        display.draw_rounded_rect(x, y, w, h, BLOCK_SIZE / 2, PAUSE_SHADE);
+        let mut text = CharsBuf::<8>::new();
+        write!(text, "PAUSED").unwrap();
+        display.set_font_scale(2);
+        let (tw, th) = display.measure_chars(text.chars());
+        let (tx, ty) = (x + (w - tw) / 2, y + (h - th) / 2);
+        // a transparent background keeps the panel visible between the letters
+        let clear = Color::RGBA32(0);
+        display.draw_text_at(tx, ty, text.chars(), Color::White, clear);
+        display.set_font_scale(1);
+    }
+
+    // draw_snake draws the first snake, fading from color at the head to
+    // dark at the tail
+    fn draw_snake(&self, display: &mut Display, color: Color, dark: Color) {
+        // draw snake head, looking where it goes
+        let (head, bg) = (self.snake_head, display.background_color);
+        let sprite = head_sprite(self.direction);
+        display.draw_bitmap((head.x, head.y, 8, 8), sprite, color, bg);
+        // draw snake, the body fades from the head to the tail, the back of
+        // snake_body is next to the head
+        let len = self.snake_body.len();
+        for (i, part) in self.snake_body.iter().enumerate() {
+            let fill = Color::lerp(color, dark, ((len - 1 - i) * 255 / len) as u8);
+            display.write_block_colored_border(part.x, part.y, fill, dark);
+        }
+    }
+
+    // wrap moves an out of bounds head to the opposite side of the play area,
+    // keeping it aligned to the grid the snake moves on
+    fn wrap(&self, mut p: Point) -> Point {
+        let head = self.snake_head;
+        match self.direction {
+            Direction::Up => {
+                p.y = head.y + (self.height - 2 * BLOCK_SIZE - 1 - head.y) / BLOCK_SIZE * BLOCK_SIZE
+            }
+            Direction::Down => p.y = head.y - (head.y - BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE,
+            Direction::Left => {
+                p.x = head.x + (self.width - 2 * BLOCK_SIZE - 1 - head.x) / BLOCK_SIZE * BLOCK_SIZE
+            }
+            Direction::Right => p.x = head.x - (head.x - BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE,
+        }
+        p
+    }
+
+    // step_replay moves the replay on by REPLAY_SPEED steps, turning the
+    // snake on the recorded ticks
+    fn step_replay(&mut self) -> GameState {
+        let replay = match self.replay.as_mut() {
+            Some(replay) => replay,
+            None => return GameState::GameOver,
+        };
+        let mut state = GameState::Live;
+        for _ in 0..REPLAY_SPEED {
+            while let Some((tick, direction)) = self.recording.get(self.replay_pos) {
+                if *tick > replay.tick {
+                    break;
+                }
+                replay.set_direction_if_valid(*direction);
+                self.replay_pos += 1;
+            }
+            state = replay.step();
+            if state == GameState::GameOver {
+                break;
+            }
+        }
+        state
+    }
+
+    // move_snake moves the first snake one block forward, eats what's there
+    // and checks if it crashed. Dead snakes stay where they are.
+    fn move_snake(&mut self) {
+        if !self.snake_alive {
+            return;
+        }
+        // update snake head, at the screen edge it stays at 0, which is out of
+        // bounds anyway
+        let mut new_head = self.snake_head;
+        if self.direction == Direction::Up {
+            new_head.y = new_head.y.saturating_sub(BLOCK_SIZE);
+        } else if self.direction == Direction::Right {
+            new_head.x += BLOCK_SIZE;
+        } else if self.direction == Direction::Down {
+            new_head.y += BLOCK_SIZE;
+        } else if self.direction == Direction::Left {
+            new_head.x = new_head.x.saturating_sub(BLOCK_SIZE);
+        }
+        self.last_direction = self.direction;
+        // check the border before wrapping, so both modes see the real position
+        if self.is_out_of_bounds(new_head) {
+            match self.collision_mode {
+                CollisionMode::WallDeath => {
+                    self.snake_alive = false;
+                    return;
+                }
+                CollisionMode::Wrap => new_head = self.wrap(new_head),
+            }
+        }
+
+        self.snake_body.push_back(self.snake_head);
+        self.snake_head = new_head;
+        let eaten = self
+            .food
+            .iter()
+            .position(|(food, _)| *food == self.snake_head);
+        if let Some(i) = eaten {
+            serial_println!("found food!!!");
+            let (_, kind) = self.food.swap_remove(i);
+            self.eat(kind);
+        } else if self.is_bonus_food(self.snake_head) {
+            serial_println!("found bonus food!!!");
+            self.remove_bonus_food();
+            self.grow(BONUS_POINTS);
+        }
+        // the body is one segment shorter than the snake, without the head
+        while self.snake_body.len() >= self.snake_length {
+            if let Some(tail) = self.snake_body.pop_front() {
+                self.snake_tail.push(tail);
+            }
+        }
+        if self.snake_body.contains(&self.snake_head)
+            || self.is_snake2(self.snake_head)
+            || self.obstacles.contains(&self.snake_head)
+        {
+            self.snake_alive = false;
         }
-        self.food = Some(point);
     }
 
     // rand implements a simple pseudo random number generator
@@ -72,140 +715,1014 @@ impl World {
         let result = self.rng.next_u64() as usize % max;
         result
     }
+
+    // handle_key steers the snakes and toggles the pause
+    fn handle_key(&mut self, key: pc_keyboard::DecodedKey) {
+        match key {
+            pc_keyboard::DecodedKey::Unicode(' ') if !self.game_over => {
+                self.paused = !self.paused;
+            }
+            // the snakes can't be steered while the game is paused
+            _ if self.paused => {}
+            pc_keyboard::DecodedKey::Unicode(character) => {
+                let direction = match character {
+                    'a' => Direction::Left,
+                    'd' => Direction::Right,
+                    'w' => Direction::Up,
+                    's' => Direction::Down,
+                    _ => return,
+                };
+                // WASD belongs to the second snake if there is one
+                if self.two_player {
+                    self.swap_snakes();
+                    self.set_direction_if_valid(direction);
+                    self.swap_snakes();
+                } else {
+                    self.set_direction_if_valid(direction);
+                }
+            }
+            pc_keyboard::DecodedKey::RawKey(key) => match key {
+                pc_keyboard::KeyCode::ArrowLeft => self.set_direction_if_valid(Direction::Left),
+                pc_keyboard::KeyCode::ArrowRight => self.set_direction_if_valid(Direction::Right),
+                pc_keyboard::KeyCode::ArrowUp => self.set_direction_if_valid(Direction::Up),
+                pc_keyboard::KeyCode::ArrowDown => self.set_direction_if_valid(Direction::Down),
+                _ => {}
+            },
+        }
+    }
+
+    // draw_world draws the arena and the snakes of this world
+    fn draw_world(&mut self, display: &mut Display) {
+        // if game is over, let the head blink until the game over screen
+        if self.game_over {
+            let ticks = self.transition_ticks.unwrap_or(0);
+            let color = if ticks % 10 < 5 {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            display.write_block(self.snake_head.x, self.snake_head.y, color);
+            if self.two_player {
+                display.write_block(self.snake2_head.x, self.snake2_head.y, color);
+            }
+            return;
+        }
+        if self.paused {
+            if self.pause_snapshot.is_none() {
+                self.draw_paused(display);
+            }
+            return;
+        }
+        if let Some(snapshot) = self.pause_snapshot.take() {
+            let (x, y, w, h) = self.pause_region();
+            display.restore_region(x, y, w, h, &snapshot);
+        }
+
+        if !self.borders_drawn {
+            display.draw_borders_with_color(self.border_color());
+            self.borders_drawn = true;
+        }
+        if self.score_dirty {
+            self.draw_score(display);
+            self.score_dirty = false;
+        }
+        // keep the blocks below off the border
+        let (x, y, w, h) = self.arena();
+        display.set_clip_rect(x, y, w, h);
+        // draw food
+        for (food, kind) in self.food.iter() {
+            let bg = display.background_color;
+            display.draw_bitmap((food.x, food.y, 8, 8), APPLE, kind.color(), bg);
+        }
+        // save the food, the erasing below may clear it when it spawned where
+        // the banner or the tail was
+        if display.has_back_buffer() {
+            for (food, _) in self.food.iter() {
+                display.copy_rect_to_back(food.x, food.y, BLOCK_SIZE, BLOCK_SIZE);
+            }
+        }
+        // the blocks below the banner are drawn again right after
+        if self.banner_drawn && self.banner_ticks_remaining == 0 {
+            let (x, y, w, h) = self.banner_region();
+            display.clear_rect(x, y, w, h);
+            self.banner_drawn = false;
+        }
+        for tail in self.snake_tail.drain(..) {
+            display.clear_rect(tail.x, tail.y, BLOCK_SIZE, BLOCK_SIZE);
+        }
+        if display.has_back_buffer() {
+            for (food, _) in self.food.iter() {
+                display.copy_rect_from_back(food.x, food.y, BLOCK_SIZE, BLOCK_SIZE);
+            }
+        }
+        for obstacle in self.obstacles.iter() {
+            display.write_block(obstacle.x, obstacle.y, Color::DarkYellow);
+        }
+        // bonus food is twice the size of other food
+        if let Some((bonus, _)) = self.bonus_food {
+            display.draw_rect(
+                bonus.x,
+                bonus.y,
+                2 * BLOCK_SIZE,
+                2 * BLOCK_SIZE,
+                Color::Yellow,
+            );
+            display.draw_rect_border(
+                bonus.x,
+                bonus.y,
+                2 * BLOCK_SIZE,
+                2 * BLOCK_SIZE,
+                Color::White,
+            );
+        }
+        self.draw_snake(display, Color::Green, Color::DarkGreen);
+        if self.two_player {
+            self.swap_snakes();
+            self.draw_snake(display, Color::Cyan, Color::DarkCyan);
+            self.swap_snakes();
+        }
+        if self.banner_ticks_remaining > 0 {
+            self.draw_banner(display);
+        }
+        display.clear_clip();
+    }
 }
 
 impl Game for World {
     fn reset(&mut self, width: usize, height: usize) {
         self.width = width;
         self.height = height;
-        self.food = None;
         self.score = 0;
         self.game_over = false;
-        self.speed = 2;
+        // start at half speed, a move every other tick
+        self.speed = self.max_speed / 2;
         self.direction = Direction::Right;
+        self.last_direction = Direction::Right;
         self.snake_length = 1;
         self.snake_head = Point {
             x: width / 2,
             y: height / 2,
         };
-        self.snake_body = VecDeque::new();
-        self.snake_tail = None;
+        self.snake_body.clear();
+        self.snake_alive = true;
+        self.snake2_head = snake2_start(width, height);
+        self.snake2_body.clear();
+        self.snake2_direction = Direction::Left;
+        self.snake2_last_direction = Direction::Left;
+        self.snake2_length = 1;
+        self.snake2_score = 0;
+        self.snake2_alive = true;
+        self.snake_tail.clear();
         self.counter = 0;
+        self.tick = 0;
+        self.transition_ticks = None;
+        self.borders_drawn = false;
+        self.score_dirty = true;
+        self.food.clear();
+        self.bonus_food = None;
+        self.obstacles.clear();
+        self.level = 1;
+        self.banner_ticks_remaining = 0;
+        self.banner_drawn = false;
+        self.paused = false;
+        self.pause_snapshot = None;
+        if self.mode == SnakeMode::Playback {
+            self.mode = SnakeMode::Recording;
+        }
+        self.recording.clear();
+        self.replay = None;
+        self.start_rng = self.rng.clone();
+        self.fill_food();
     }
 
     // step moves the snake one step forward
     fn step(&mut self) -> GameState {
-        self.counter += 1;
-        if self.counter % self.speed as u64 != 0 {
-            return GameState::Live;
+        if self.mode == SnakeMode::Playback {
+            return self.step_replay();
         }
         if self.game_over {
-            return GameState::GameOver;
+            return transition(&mut self.transition_ticks);
         }
-        // update snake head
-        let mut new_head = self.snake_head;
-        if self.direction == Direction::Up {
-            new_head.y -= BLOCK_SIZE;
-        } else if self.direction == Direction::Right {
-            new_head.x += BLOCK_SIZE;
-        } else if self.direction == Direction::Down {
-            new_head.y += BLOCK_SIZE;
-        } else if self.direction == Direction::Left {
-            new_head.x -= BLOCK_SIZE;
+        if self.paused {
+            return GameState::Live;
         }
-        new_head.x = new_head.x % self.width;
-        new_head.y = new_head.y % self.height;
-
-        self.snake_body.push_back(self.snake_head);
-        self.snake_head = new_head;
-        if let Some(food) = self.food {
-            if self.snake_head == food {
-                serial_println!("found food!!!");
-                self.score += 1;
-                self.food = None;
-                self.snake_length += 1;
-            } else {
-                self.snake_tail = self.snake_body.pop_front();
+        self.tick += 1;
+        self.banner_ticks_remaining = self.banner_ticks_remaining.saturating_sub(1);
+        if let Some((_, expiry)) = self.bonus_food {
+            if self.tick >= expiry {
+                self.remove_bonus_food();
             }
-        } else {
-            self.place_random_food();
-            self.snake_tail = self.snake_body.pop_front();
         }
-        if self.snake_head.x >= self.width - 2 * BLOCK_SIZE
-            || self.snake_head.y >= self.height - 2 * BLOCK_SIZE
-            || self.snake_head.x <= BLOCK_SIZE
-            || self.snake_head.y <= BLOCK_SIZE
-        {
-            self.game_over = true;
+        // counter collects speed per tick and pays max_speed per move
+        self.counter += self.speed as u64;
+        if self.counter < self.max_speed as u64 {
+            return GameState::Live;
         }
-        for body in self.snake_body.iter() {
-            if self.snake_head == *body {
-                self.game_over = true;
-            }
+        self.counter -= self.max_speed as u64;
+        self.move_snake();
+        if self.two_player {
+            self.swap_snakes();
+            self.move_snake();
+            self.swap_snakes();
         }
+        self.fill_food();
 
+        let alive = self.snake_alive || (self.two_player && self.snake2_alive);
+        self.game_over = !alive;
         if self.game_over {
-            GameState::GameOver
+            transition(&mut self.transition_ticks)
         } else {
             GameState::Live
         }
     }
 
     fn on_keypress(&mut self, key: pc_keyboard::DecodedKey) {
-        let w = self;
-        match key {
-            pc_keyboard::DecodedKey::Unicode(character) => match character {
-                'a' => {
-                    if w.direction != Direction::Right {
-                        w.direction = Direction::Left;
-                    }
-                }
-                'd' => {
-                    if w.direction != Direction::Left {
-                        w.direction = Direction::Right;
-                    }
+        if self.mode == SnakeMode::Playback {
+            return;
+        }
+        let direction = self.direction;
+        self.handle_key(key);
+        if self.mode == SnakeMode::Recording && self.direction != direction {
+            self.recording.push((self.tick, self.direction));
+        }
+    }
+
+    fn draw(&mut self, display: &mut Display) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.draw(display);
+            return;
+        }
+        self.draw_world(display);
+    }
+
+    fn score(&self) -> u64 {
+        self.score.max(self.snake2_score) as u64
+    }
+
+    // the first snake of a single player game can be replayed
+    fn can_replay(&self) -> bool {
+        self.game_over && !self.two_player && self.mode != SnakeMode::Live
+    }
+
+    fn start_replay(&mut self) {
+        if !self.can_replay() {
+            return;
+        }
+        let mut replay = World::new(self.width, self.height);
+        replay.mode = SnakeMode::Live;
+        replay.max_food = self.max_food;
+        replay.max_speed = self.max_speed;
+        replay.collision_mode = self.collision_mode;
+        replay.rng = self.start_rng.clone();
+        replay.reset(self.width, self.height);
+        self.replay = Some(alloc::boxed::Box::new(replay));
+        self.replay_pos = 0;
+        self.mode = SnakeMode::Playback;
+    }
+
+    fn serial_dump(&self) {
+        // row by row, the whole grid doesn't fit in a fixed size buffer
+        for row in 0..self.grid_height() {
+            for c in self.grid_row(row) {
+                serial_print!("{}", c);
+            }
+            serial_println!();
+        }
+    }
+}
+
+// block_square returns the blocks of the size x size square with its top
+// left block at p
+fn block_square(p: Point, size: usize) -> Vec<Point> {
+    let mut blocks = Vec::with_capacity(size * size);
+    for dy in 0..size {
+        for dx in 0..size {
+            blocks.push(Point {
+                x: p.x + dx * BLOCK_SIZE,
+                y: p.y + dy * BLOCK_SIZE,
+            });
+        }
+    }
+    blocks
+}
+
+// is_ahead reports whether p lies within OBSTACLE_CLEARANCE blocks of head
+// in direction
+fn is_ahead(head: Point, direction: Direction, p: Point) -> bool {
+    let reach = OBSTACLE_CLEARANCE * BLOCK_SIZE;
+    match direction {
+        Direction::Up => p.x == head.x && p.y < head.y && head.y - p.y <= reach,
+        Direction::Down => p.x == head.x && p.y > head.y && p.y - head.y <= reach,
+        Direction::Left => p.y == head.y && p.x < head.x && head.x - p.x <= reach,
+        Direction::Right => p.y == head.y && p.x > head.x && p.x - head.x <= reach,
+    }
+}
+
+// snake2_start returns where the second snake starts, four blocks below the
+// first one
+fn snake2_start(width: usize, height: usize) -> Point {
+    Point {
+        x: width / 2,
+        y: height / 2 + 4 * BLOCK_SIZE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::tests::pixel;
+    use crate::world::TRANSITION_TICKS;
+    use pc_keyboard::{DecodedKey, KeyCode};
+
+    fn world_at(x: usize, y: usize, direction: Direction, mode: CollisionMode) -> World {
+        let mut world = World::new(320, 240);
+        world.reset(320, 240);
+        world.speed = world.max_speed;
+        world.snake_head = Point { x, y };
+        world.direction = direction;
+        world.last_direction = direction;
+        world.collision_mode = mode;
+        world
+    }
+
+    #[test]
+    fn test_reverse_direction_rejected() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.queued_direction(), Direction::Right);
+        world.on_keypress(DecodedKey::Unicode('a'));
+        assert_eq!(world.queued_direction(), Direction::Right);
+
+        world.on_keypress(DecodedKey::Unicode('w'));
+        assert_eq!(world.queued_direction(), Direction::Up);
+        // up then left before the next step would turn the snake into its
+        // neck, it is still moving right
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.queued_direction(), Direction::Up);
+
+        world.step();
+        world.set_direction_if_valid(Direction::Down);
+        assert_eq!(world.queued_direction(), Direction::Up);
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(world.queued_direction(), Direction::Left);
+    }
+
+    #[test]
+    fn test_to_grid_string() {
+        let mut world = World::new(80, 64);
+        world.reset(80, 64);
+        world.food = alloc::vec![(Point { x: 56, y: 32 }, FoodKind::Regular)];
+
+        let grid = world.to_grid_string();
+        let chars = &grid;
+        let line = world.grid_width() + 1;
+        assert_eq!(grid.len(), line * world.grid_height());
+        assert_eq!(chars[4 * line + 5], 'H');
+        assert_eq!(chars[4 * line + 7], 'F');
+        assert_eq!(chars[4 * line + 6], '.');
+        assert_eq!(chars[0], '#');
+        assert_eq!(chars[line - 1], '\n');
+    }
+
+    #[test]
+    fn test_wall_death_at_border() {
+        let cases = [
+            (2 * BLOCK_SIZE, 120, Direction::Left),
+            (320 - 3 * BLOCK_SIZE, 120, Direction::Right),
+            (160, 2 * BLOCK_SIZE, Direction::Up),
+            (160, 240 - 3 * BLOCK_SIZE, Direction::Down),
+        ];
+        for (x, y, direction) in cases.iter() {
+            let mut world = world_at(*x, *y, *direction, CollisionMode::WallDeath);
+            assert_eq!(world.step(), GameState::Transitioning(TRANSITION_TICKS));
+        }
+    }
+
+    #[test]
+    fn test_transition_to_game_over() {
+        let mut world = world_at(
+            2 * BLOCK_SIZE,
+            120,
+            Direction::Left,
+            CollisionMode::WallDeath,
+        );
+        assert_eq!(world.step(), GameState::Transitioning(60));
+        for i in 1..60 {
+            assert_eq!(world.step(), GameState::Transitioning(60 - i));
+        }
+        assert_eq!(world.step(), GameState::GameOver);
+        assert_eq!(world.step(), GameState::GameOver);
+    }
+
+    #[test]
+    fn test_wall_death_inside() {
+        let mut world = world_at(
+            3 * BLOCK_SIZE,
+            120,
+            Direction::Left,
+            CollisionMode::WallDeath,
+        );
+        assert_eq!(world.step(), GameState::Live);
+        assert_eq!(
+            world.snake_head,
+            Point {
+                x: 2 * BLOCK_SIZE,
+                y: 120
+            }
+        );
+    }
+
+    #[test]
+    fn test_wrap_at_border() {
+        let cases = [
+            (
+                2 * BLOCK_SIZE,
+                120,
+                Direction::Left,
+                320 - 3 * BLOCK_SIZE,
+                120,
+            ),
+            (
+                320 - 3 * BLOCK_SIZE,
+                120,
+                Direction::Right,
+                2 * BLOCK_SIZE,
+                120,
+            ),
+            (
+                160,
+                2 * BLOCK_SIZE,
+                Direction::Up,
+                160,
+                240 - 3 * BLOCK_SIZE,
+            ),
+            (
+                160,
+                240 - 3 * BLOCK_SIZE,
+                Direction::Down,
+                160,
+                2 * BLOCK_SIZE,
+            ),
+        ];
+        for (x, y, direction, want_x, want_y) in cases.iter() {
+            let mut world = world_at(*x, *y, *direction, CollisionMode::Wrap);
+            assert_eq!(world.step(), GameState::Live);
+            assert_eq!(
+                world.snake_head,
+                Point {
+                    x: *want_x,
+                    y: *want_y
                 }
-                'w' => {
-                    if w.direction != Direction::Down {
-                        w.direction = Direction::Up;
-                    }
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrap_left_boundary() {
+        // heads on the very edge of the screen, where subtracting a block
+        // underflows, still wrap to the opposite edge of the play area
+        let cases = [
+            (0, 120, Direction::Left, 320 - 3 * BLOCK_SIZE, 120),
+            (320 - BLOCK_SIZE, 120, Direction::Right, 2 * BLOCK_SIZE, 120),
+            (160, 0, Direction::Up, 160, 240 - 3 * BLOCK_SIZE),
+            (160, 240 - BLOCK_SIZE, Direction::Down, 160, 2 * BLOCK_SIZE),
+        ];
+        for (x, y, direction, want_x, want_y) in cases.iter() {
+            let mut world = world_at(*x, *y, *direction, CollisionMode::Wrap);
+            assert_eq!(world.step(), GameState::Live);
+            assert_eq!(
+                world.snake_head,
+                Point {
+                    x: *want_x,
+                    y: *want_y
                 }
-                's' => {
-                    if w.direction != Direction::Up {
-                        w.direction = Direction::Down;
-                    }
+            );
+        }
+    }
+
+    #[test]
+    fn test_head_sprites_mirror() {
+        for (a, b) in HEAD_RIGHT.iter().zip(HEAD_LEFT.iter()) {
+            assert_eq!(a.reverse_bits(), *b);
+        }
+        assert!(HEAD_UP.iter().eq(HEAD_DOWN.iter().rev()));
+        assert!(head_sprite(Direction::Up).eq(HEAD_UP));
+    }
+
+    #[test]
+    fn test_score_redrawn_when_changed() {
+        let mut display = Display::new_for_test(320, 240);
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        assert!(world.score_dirty);
+        world.draw(&mut display);
+        assert!(!world.score_dirty);
+
+        let food = Point {
+            x: 160 + BLOCK_SIZE,
+            y: 120,
+        };
+        world.food = alloc::vec![(food, FoodKind::Regular)];
+        world.step();
+        assert_eq!(world.score, 1);
+        assert!(world.score_dirty);
+        world.draw(&mut display);
+        assert!(!world.score_dirty);
+
+        world.step();
+        assert!(!world.score_dirty);
+
+        // the bar shares the line of the score, above the border
+        let (x, y, w, h) = world.progress_bar_rect();
+        assert!(y + h <= BLOCK_SIZE);
+        assert!(x >= 2 * BLOCK_SIZE + 20 * 8 && x + w <= 320 - 2 * BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_speed_increases_with_score() {
+        let mut world = World::new(320, 240);
+        world.reset(320, 240);
+        world.collision_mode = CollisionMode::Wrap;
+        assert_eq!(world.speed, 5);
+
+        // at half speed the snake moves every other tick
+        let start = world.snake_head;
+        world.step();
+        assert_eq!(world.snake_head, start);
+        world.step();
+        assert_ne!(world.snake_head, start);
+
+        let mut speeds = Vec::new();
+        for _ in 0..30 {
+            let score = world.score;
+            while world.score == score {
+                // put the food right in front of the head
+                let mut next = world.snake_head;
+                next.x += BLOCK_SIZE;
+                if world.is_out_of_bounds(next) {
+                    next = world.wrap(next);
                 }
-                _ => (),
-            },
-            pc_keyboard::DecodedKey::RawKey(key) => match key {
-                pc_keyboard::KeyCode::ArrowLeft => w.direction = Direction::Left,
-                pc_keyboard::KeyCode::ArrowRight => w.direction = Direction::Right,
-                pc_keyboard::KeyCode::ArrowUp => w.direction = Direction::Up,
-                pc_keyboard::KeyCode::ArrowDown => w.direction = Direction::Down,
-                _ => {}
-            },
+                world.food = alloc::vec![(next, FoodKind::Regular)];
+                // keep the path clear of the obstacles the score brings
+                world.obstacles.clear();
+                world.step();
+            }
+            speeds.push(world.speed);
         }
+        assert_eq!(speeds[3], 5);
+        assert_eq!(speeds[4], 6);
+        assert_eq!(speeds[24], 10);
+        assert_eq!(speeds[29], 10);
     }
 
-    fn draw(&mut self, display: &mut Display) {
-        // if game is over, print "GAME OVER"
-        if self.game_over {
-            return;
+    #[test]
+    fn test_multiple_food_items() {
+        let mut world = World::new(320, 240).with_food_count(3);
+        world.reset(320, 240);
+        world.speed = world.max_speed;
+        assert_eq!(world.food.len(), 3);
+        assert!(world.food.iter().all(|(f, _)| !world.is_out_of_bounds(*f)));
+        assert!(world.food.iter().all(|(f, _)| *f != world.snake_head));
+
+        // eating one of them replaces it with a new one
+        let eaten = Point {
+            x: world.snake_head.x + BLOCK_SIZE,
+            y: world.snake_head.y,
+        };
+        world.food[1] = (eaten, FoodKind::Regular);
+        let others = [world.food[0], world.food[2]];
+        world.step();
+        assert_eq!(world.score, 1);
+        assert_eq!(world.food.len(), 3);
+        assert!(others.iter().all(|f| world.food.contains(f)));
+        assert!(world.food.iter().all(|(f, _)| *f != eaten));
+    }
+
+    #[test]
+    fn test_growth_stops_at_max_length() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        for _ in 1..MAX_SNAKE_LENGTH {
+            world.snake_body.push_back(Point { x: 0, y: 0 });
         }
+        world.snake_length = MAX_SNAKE_LENGTH;
+        for i in 1..=2 {
+            let food = Point {
+                x: 160 + i * BLOCK_SIZE,
+                y: 120,
+            };
+            world.food = alloc::vec![(food, FoodKind::Regular)];
+            world.step();
+            assert_eq!(world.snake_head, food);
+            assert_eq!(world.snake_length, MAX_SNAKE_LENGTH);
+            assert_eq!(world.snake_body.len(), MAX_SNAKE_LENGTH - 1);
+        }
+    }
 
-        // draw food
-        if let Some(food) = self.food {
-            display.write_block(food.x, food.y, Color::LightRed);
+    #[test]
+    fn test_poison_shrinks_snake() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        for i in (1..5).rev() {
+            world.snake_body.push_back(Point {
+                x: 160 - i * BLOCK_SIZE,
+                y: 120,
+            });
+        }
+        world.snake_length = 5;
+        let poison = Point {
+            x: 160 + BLOCK_SIZE,
+            y: 120,
+        };
+        world.food = alloc::vec![(poison, FoodKind::Poison)];
+
+        world.step();
+        assert_eq!(world.snake_length, 3);
+        assert_eq!(world.snake_body.len(), 2);
+        assert_eq!(world.snake_head, poison);
+        assert_eq!(world.score, 0);
+        // the three segments behind the new tail get erased
+        assert_eq!(world.snake_tail.len(), 3);
+
+        // but never below the head
+        world.snake_length = 2;
+        let poison = Point {
+            x: 160 + 2 * BLOCK_SIZE,
+            y: 120,
+        };
+        world.food = alloc::vec![(poison, FoodKind::Poison)];
+        world.step();
+        assert_eq!(world.snake_length, 1);
+        assert!(world.snake_body.is_empty());
+    }
+
+    #[test]
+    fn test_obstacles_stay_out_of_the_way() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        for _ in 0..200 {
+            world.level_up();
+        }
+        for step in 1..=OBSTACLE_CLEARANCE {
+            assert!(!world
+                .obstacles
+                .contains(&world.snake_head.right(step * BLOCK_SIZE)));
+        }
+    }
+
+    #[test]
+    fn test_full_arena_gives_up() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        world.food.clear();
+        for y in (0..world.height).step_by(BLOCK_SIZE) {
+            for x in (0..world.width).step_by(BLOCK_SIZE) {
+                world.obstacles.push(Point { x, y });
+            }
+        }
+        assert_eq!(world.random_free_point(1), None);
+        world.fill_food();
+        assert!(world.food.is_empty());
+    }
+
+    #[test]
+    fn test_bonus_food() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        world.max_speed = 2 * world.speed;
+        world.score = 4;
+        // the head runs into the lower left block of the bonus food
+        let bonus = Point {
+            x: 160 + BLOCK_SIZE,
+            y: 120 - BLOCK_SIZE,
+        };
+        world.food.clear();
+        world.max_food = 0;
+        world.bonus_food = Some((bonus, 100));
+        let speed = world.speed;
+        // the snake moves on every other tick now
+        world.step();
+        world.step();
+        assert_eq!(world.score, 4 + BONUS_POINTS);
+        assert_eq!(world.snake_length, 2);
+        assert_eq!(world.bonus_food, None);
+        assert_eq!(world.snake_tail.len(), 4);
+        // passing a multiple of LEVEL_FOOD speeds up, even if skipped
+        assert_eq!(world.speed, speed + 1);
+    }
+
+    #[test]
+    fn test_bonus_food_expires() {
+        let mut world = world_at(160, 120, Direction::Down, CollisionMode::Wrap);
+        world.max_food = 0;
+        world.food.clear();
+        let bonus = Point { x: 80, y: 40 };
+        world.bonus_food = Some((bonus, world.tick + BONUS_TICKS));
+        for _ in 0..BONUS_TICKS - 1 {
+            world.step();
+        }
+        assert!(world.bonus_food.is_some());
+        world.step();
+        assert_eq!(world.bonus_food, None);
+        assert!(world.snake_tail.contains(&Point { x: 88, y: 48 }));
+        assert_eq!(world.score, 0);
+
+        // the bonus food is drawn at twice the block size
+        let mut display = Display::new_for_test(320, 240);
+        world.bonus_food = Some((bonus, world.tick + BONUS_TICKS));
+        world.draw(&mut display);
+        let grid = world.to_grid_string();
+        let line = world.grid_width() + 1;
+        assert_eq!(grid[6 * line + 11], 'F');
+        assert_eq!(grid[6 * line + 12], '.');
+    }
+
+    #[test]
+    fn test_tail_erase_keeps_food() {
+        let mut display = Display::new_for_test(320, 240);
+        assert!(display.enable_back_buffer());
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::Wrap);
+        world.food.clear();
+        world.max_food = 0;
+        world.draw(&mut display);
+        world.step();
+
+        // food spawned where the tail is erased in the same frame
+        world
+            .food
+            .push((Point { x: 160, y: 120 }, FoodKind::Regular));
+        world.draw(&mut display);
+        assert_ne!(pixel(&display, 161, 122), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_skipped_frames_erase_tail() {
+        let mut display = Display::new_for_test(320, 240);
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::Wrap);
+        world.food.clear();
+        world.max_food = 0;
+        world.draw(&mut display);
+        assert_eq!(pixel(&display, 161, 121), (0, 255, 0));
+
+        // steps without a frame in between, as with a frame rate limit
+        for _ in 0..3 {
+            world.step();
         }
-        if let Some(tail) = self.snake_tail {
-            display.write_block(tail.x, tail.y, Color::Black);
-            self.snake_tail = None;
+        world.draw(&mut display);
+        for x in [160, 160 + BLOCK_SIZE, 160 + 2 * BLOCK_SIZE].iter() {
+            assert_eq!(pixel(&display, x + 1, 121), (0, 0, 0));
         }
-        // draw snake head
-        display.write_block(self.snake_head.x, self.snake_head.y, Color::Green);
-        // draw snake
-        for part in self.snake_body.iter() {
-            display.write_block(part.x, part.y, Color::LightGreen);
+        assert_eq!(pixel(&display, 160 + 3 * BLOCK_SIZE + 1, 121), (0, 255, 0));
+    }
+
+    #[test]
+    fn test_two_players() {
+        let mut world = World::new(320, 240).with_two_players();
+        world.reset(320, 240);
+        world.speed = world.max_speed;
+        world.food.clear();
+        world.max_food = 0;
+        world.bonus_food = None;
+        let (start1, start2) = (world.snake_head, world.snake2_head);
+
+        // arrows steer the first snake, WASD the second
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowUp));
+        world.on_keypress(DecodedKey::Unicode('s'));
+        assert_eq!(world.step(), GameState::Live);
+        assert_eq!(world.snake_head, start1.up(BLOCK_SIZE));
+        assert_eq!(world.snake2_head, start2.down(BLOCK_SIZE));
+        assert_eq!(world.direction, Direction::Up);
+        assert_eq!(world.snake2_direction, Direction::Down);
+
+        let grid = world.to_grid_string();
+        let line = world.grid_width() + 1;
+        let (head2, row2) = (start2.x / BLOCK_SIZE, start2.y / BLOCK_SIZE + 1);
+        assert_eq!(grid[row2 * line + head2], 'h');
+    }
+
+    #[test]
+    fn test_two_players_collision() {
+        let mut world = World::new(320, 240).with_two_players();
+        world.reset(320, 240);
+        world.speed = world.max_speed;
+        world.food.clear();
+        world.max_food = 0;
+        world.bonus_food = None;
+        // the first snake runs into the body of the second one
+        world.snake_head = Point { x: 80, y: 80 };
+        world.direction = Direction::Right;
+        world.snake2_head = Point { x: 88, y: 40 };
+        world.snake2_direction = Direction::Up;
+        world.snake2_length = 8;
+        world.snake2_body = (0..7)
+            .map(|i| Point {
+                x: 88,
+                y: 96 - i * BLOCK_SIZE,
+            })
+            .collect();
+        assert_eq!(world.step(), GameState::Live);
+        assert!(!world.snake_alive);
+        assert!(world.snake2_alive);
+        assert!(!world.game_over);
+
+        // the game goes on until the second snake dies as well
+        let head = world.snake_head;
+        world.collision_mode = CollisionMode::WallDeath;
+        let mut steps = 0;
+        let mut state = GameState::Live;
+        while world.snake2_alive {
+            assert_eq!(state, GameState::Live);
+            state = world.step();
+            assert_eq!(world.snake_head, head);
+            steps += 1;
+            assert!(steps < 10);
+        }
+        assert!(world.game_over);
+        assert!(matches!(state, GameState::Transitioning(_)));
+    }
+
+    #[test]
+    fn test_obstacle_collision() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        world.obstacles.push(Point {
+            x: 160 + BLOCK_SIZE,
+            y: 120,
+        });
+        assert_eq!(world.step(), GameState::Transitioning(TRANSITION_TICKS));
+        let mut state = GameState::Live;
+        for _ in 0..TRANSITION_TICKS {
+            state = world.step();
         }
+        assert_eq!(state, GameState::GameOver);
+
+        world.reset(320, 240);
+        assert!(world.obstacles.is_empty());
+    }
+
+    #[test]
+    fn test_level_up() {
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        let speed = world.max_speed / 2;
+        world.speed = speed;
+        world.grow(4);
+        assert_eq!(world.level, 1);
+        assert!(world.obstacles.is_empty());
+        world.grow(1);
+        assert_eq!(world.level, 2);
+        assert_eq!(world.speed, speed + 1);
+        assert_eq!(world.obstacles.len(), 1);
+        assert_eq!(world.banner_ticks_remaining, BANNER_TICKS);
+        let obstacle = world.obstacles[0];
+        assert!(!world.is_free(obstacle));
+        assert!(!world.food.iter().any(|(food, _)| *food == obstacle));
+
+        // the banner is cleared once it ran out
+        let mut display = Display::new_for_test(320, 240);
+        world.draw(&mut display);
+        assert!(world.banner_drawn);
+        world.banner_ticks_remaining = 1;
+        world.obstacles.clear();
+        world.step();
+        world.draw(&mut display);
+        assert!(!world.banner_drawn);
+        let (x, y, w, h) = world.banner_region();
+        let below = (x..x + w).any(|x| (y..y + h).any(|y| pixel(&display, x, y) == (255, 255, 0)));
+        assert!(!below);
+    }
+
+    #[test]
+    fn test_body_gradient() {
+        let mut display = Display::new_for_test(320, 240);
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        world.food.clear();
+        world.snake_length = 4;
+        world.snake_body = (1..4)
+            .rev()
+            .map(|i| Point {
+                x: 160 - i * BLOCK_SIZE,
+                y: 120,
+            })
+            .collect();
+        world.draw(&mut display);
+
+        // the segment next to the head is green, fading to dark at the tail
+        let center = |i: usize| pixel(&display, 160 - i * BLOCK_SIZE + 4, 124);
+        assert_eq!(center(1), (0, 255, 0));
+        assert_eq!(
+            center(2),
+            Color::lerp(Color::Green, Color::DarkGreen, 85).rgb()
+        );
+        assert_eq!(
+            center(3),
+            Color::lerp(Color::Green, Color::DarkGreen, 170).rgb()
+        );
+        // the head is always plain green
+        assert_eq!(pixel(&display, 164, 124), (0, 255, 0));
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut display = Display::new_for_test(320, 240);
+        let mut world = world_at(160, 120, Direction::Right, CollisionMode::WallDeath);
+        world.draw(&mut display);
+        let (x, y, w, h) = world.pause_region();
+        let frame = display.snapshot_region(x, y, w, h);
+
+        // space pauses, the snake neither moves nor turns
+        let head = world.snake_head;
+        world.on_keypress(DecodedKey::Unicode(' '));
+        world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowUp));
+        assert_eq!(world.step(), GameState::Live);
+        assert_eq!(world.snake_head, head);
+        assert_eq!(world.queued_direction(), Direction::Right);
+        world.draw(&mut display);
+        assert_ne!(display.snapshot_region(x, y, w, h), frame);
+
+        // space again removes the message and lets the snake move on
+        world.on_keypress(DecodedKey::Unicode(' '));
+        world.draw(&mut display);
+        assert_eq!(display.snapshot_region(x, y, w, h), frame);
+        world.step();
+        assert_eq!(world.snake_head, head.right(BLOCK_SIZE));
+    }
+
+    #[test]
+    fn test_wrap_border_dimmed() {
+        let mut display = Display::new_for_test(320, 240);
+        let mut world = World::new(320, 240).with_collision_mode(CollisionMode::Wrap);
+        world.reset(320, 240);
+        world.draw(&mut display);
+        assert_eq!(pixel(&display, BLOCK_SIZE, 120), (0x80, 0x80, 0x80));
+
+        // a snake crossing the border leaves its tail on the other side
+        world.speed = world.max_speed;
+        world.food.clear();
+        world.max_food = 0;
+        let start = Point {
+            x: 2 * BLOCK_SIZE,
+            y: 120,
+        };
+        world.snake_head = start;
+        world.direction = Direction::Left;
+        world.snake_length = 2;
+        world.step();
+        world.step();
+        let crossed = Point {
+            x: 320 - 3 * BLOCK_SIZE,
+            y: 120,
+        };
+        assert!(world.snake_tail.contains(&start));
+        assert_eq!(world.snake_body.iter().collect::<Vec<_>>(), [&crossed]);
+        assert_eq!(world.snake_head, crossed.left(BLOCK_SIZE));
+    }
+
+    #[test]
+    fn test_wall_death_at_screen_edge() {
+        // moving up or left from 0 must not underflow
+        let cases = [(0, 120, Direction::Left), (160, 0, Direction::Up)];
+        for (x, y, direction) in cases.iter() {
+            let mut world = world_at(*x, *y, *direction, CollisionMode::WallDeath);
+            assert_eq!(world.step(), GameState::Transitioning(TRANSITION_TICKS));
+            assert_eq!(world.snake_head, Point { x: *x, y: *y });
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut world = World::new(320, 240);
+        world.reset(320, 240);
+        // play a game of a few turns until the snake hits the wall
+        let turns = [
+            (7, KeyCode::ArrowUp),
+            (12, KeyCode::ArrowLeft),
+            (20, KeyCode::ArrowDown),
+        ];
+        let mut ticks = 0;
+        while world.step() != GameState::GameOver {
+            ticks += 1;
+            for (tick, key) in turns.iter() {
+                if *tick == ticks {
+                    world.on_keypress(DecodedKey::RawKey(*key));
+                }
+            }
+        }
+        assert_eq!(world.recording.len(), turns.len());
+        assert!(world.can_replay());
+
+        world.start_replay();
+        assert_eq!(world.mode, SnakeMode::Playback);
+        let mut replay_ticks = 0;
+        while world.step() != GameState::GameOver {
+            // the replay can't be steered
+            world.on_keypress(DecodedKey::RawKey(KeyCode::ArrowRight));
+            replay_ticks += 1;
+        }
+        assert_eq!(replay_ticks, ticks / REPLAY_SPEED);
+        let replay = world.replay.as_ref().unwrap();
+        assert_eq!(replay.snake_head, world.snake_head);
+        assert_eq!(replay.snake_body, world.snake_body);
+        assert_eq!(replay.food, world.food);
+        assert_eq!(replay.score, world.score);
+        assert!(replay.recording.is_empty());
+
+        // a new game records again
+        world.reset(320, 240);
+        assert_eq!(world.mode, SnakeMode::Recording);
+        assert_eq!(world.replay, None);
     }
 }
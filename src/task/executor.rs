@@ -1,12 +1,41 @@
 use super::{Task, TaskId};
+#[cfg(feature = "executor-stats")]
+use crate::serial_info;
 use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
+#[cfg(feature = "executor-stats")]
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::task::{Context, Poll, Waker};
 use crossbeam_queue::ArrayQueue;
 
+// EXECUTOR_STATS_INTERVAL is the number of ticks between two stats summaries
+#[cfg(feature = "executor-stats")]
+const EXECUTOR_STATS_INTERVAL: u64 = 1000;
+
+#[cfg(feature = "executor-stats")]
+#[derive(Debug, Default)]
+pub struct TaskStats {
+    pub poll_count: u64,
+    // wake_count is shared with the waker of the task, which counts the wakes
+    wake_count: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "executor-stats")]
+impl TaskStats {
+    pub fn wake_count(&self) -> u64 {
+        self.wake_count.load(Ordering::Relaxed)
+    }
+}
+
 pub struct Executor {
     tasks: BTreeMap<TaskId, Task>,
     task_queue: Arc<ArrayQueue<TaskId>>,
     waker_cache: BTreeMap<TaskId, Waker>,
+    #[cfg(feature = "executor-stats")]
+    stats: BTreeMap<TaskId, TaskStats>,
+    #[cfg(feature = "executor-stats")]
+    loop_iterations: u64,
+    #[cfg(feature = "executor-stats")]
+    last_summary_tick: u64,
 }
 
 impl Executor {
@@ -15,6 +44,41 @@ impl Executor {
             tasks: BTreeMap::new(),
             task_queue: Arc::new(ArrayQueue::new(100)),
             waker_cache: BTreeMap::new(),
+            #[cfg(feature = "executor-stats")]
+            stats: BTreeMap::new(),
+            #[cfg(feature = "executor-stats")]
+            loop_iterations: 0,
+            #[cfg(feature = "executor-stats")]
+            last_summary_tick: 0,
+        }
+    }
+
+    #[cfg(feature = "executor-stats")]
+    #[allow(dead_code)]
+    pub fn task_stats(&self, id: TaskId) -> Option<&TaskStats> {
+        self.stats.get(&id)
+    }
+
+    // log_stats logs a summary of the stats every EXECUTOR_STATS_INTERVAL ticks
+    #[cfg(feature = "executor-stats")]
+    fn log_stats(&mut self) {
+        self.loop_iterations += 1;
+        let ticks = super::tick::ticks();
+        if ticks < self.last_summary_tick + EXECUTOR_STATS_INTERVAL {
+            return;
+        }
+        self.last_summary_tick = ticks;
+        let most_polled = self.stats.iter().max_by_key(|(_, s)| s.poll_count);
+        let most_woken = self.stats.iter().max_by_key(|(_, s)| s.wake_count());
+        if let (Some((polled_id, polled)), Some((woken_id, woken))) = (most_polled, most_woken) {
+            serial_info!(
+                "executor: most polled task {} ({} polls), most woken task {} ({} wakes), {} loop iterations",
+                polled_id.0,
+                polled.poll_count,
+                woken_id.0,
+                woken.wake_count(),
+                self.loop_iterations
+            );
         }
     }
 
@@ -29,6 +93,8 @@ impl Executor {
     pub fn run(&mut self) -> ! {
         loop {
             self.run_ready_tasks();
+            #[cfg(feature = "executor-stats")]
+            self.log_stats();
             self.sleep_if_idle();
         }
     }
@@ -39,6 +105,9 @@ impl Executor {
             tasks,
             task_queue,
             waker_cache,
+            #[cfg(feature = "executor-stats")]
+            stats,
+            ..
         } = self;
 
         while let Ok(task_id) = task_queue.pop() {
@@ -46,15 +115,26 @@ impl Executor {
                 Some(task) => task,
                 None => continue, // task no longer exists
             };
-            let waker = waker_cache
-                .entry(task_id)
-                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
+            let waker = waker_cache.entry(task_id).or_insert_with(|| {
+                Waker::from(Arc::new(TaskWaker {
+                    task_id,
+                    task_queue: task_queue.clone(),
+                    #[cfg(feature = "executor-stats")]
+                    wake_count: stats.entry(task_id).or_default().wake_count.clone(),
+                }))
+            });
             let mut context = Context::from_waker(waker);
+            #[cfg(feature = "executor-stats")]
+            {
+                stats.entry(task_id).or_default().poll_count += 1;
+            }
             match task.poll(&mut context) {
                 Poll::Ready(()) => {
                     // task done -> remove it and its cached waker
                     tasks.remove(&task_id);
                     waker_cache.remove(&task_id);
+                    #[cfg(feature = "executor-stats")]
+                    stats.remove(&task_id);
                 }
                 Poll::Pending => {}
             }
@@ -76,17 +156,14 @@ impl Executor {
 struct TaskWaker {
     task_id: TaskId,
     task_queue: Arc<ArrayQueue<TaskId>>,
+    #[cfg(feature = "executor-stats")]
+    wake_count: Arc<AtomicU64>,
 }
 
 impl TaskWaker {
-    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
-        Waker::from(Arc::new(TaskWaker {
-            task_id,
-            task_queue,
-        }))
-    }
-
     fn wake_task(&self) {
+        #[cfg(feature = "executor-stats")]
+        self.wake_count.fetch_add(1, Ordering::Relaxed);
         self.task_queue.push(self.task_id).expect("task_queue full");
     }
 }
@@ -100,3 +177,31 @@ impl Wake for TaskWaker {
         self.wake_task();
     }
 }
+
+#[cfg(all(test, feature = "executor-stats"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_stats() {
+        let mut executor = Executor::new();
+        let task = Task::new(core::future::pending());
+        let task_id = task.id;
+        executor.spawn(task);
+        assert!(executor.task_stats(task_id).is_none());
+
+        executor.run_ready_tasks();
+        let stats = executor.task_stats(task_id).unwrap();
+        assert_eq!(stats.poll_count, 1);
+        assert_eq!(stats.wake_count(), 0);
+
+        // wakes are counted when they happen, not when the task is polled
+        executor.waker_cache[&task_id].wake_by_ref();
+        executor.waker_cache[&task_id].wake_by_ref();
+        assert_eq!(executor.task_stats(task_id).unwrap().wake_count(), 2);
+        executor.run_ready_tasks();
+        let stats = executor.task_stats(task_id).unwrap();
+        assert_eq!(stats.poll_count, 3);
+        assert_eq!(stats.wake_count(), 2);
+    }
+}
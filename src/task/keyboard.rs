@@ -1,4 +1,5 @@
-use crate::{serial_print, serial_println};
+use super::StreamInitError;
+use crate::{serial_error, serial_print, serial_println};
 use conquer_once::spin::OnceCell;
 use core::{
     pin::Pin,
@@ -34,11 +35,24 @@ pub struct ScancodeStream {
 }
 
 impl ScancodeStream {
-    pub fn new() -> Self {
-        SCANCODE_QUEUE
-            .try_init_once(|| ArrayQueue::new(100))
-            .expect("ScancodeStream::new should only be called once");
-        ScancodeStream { _private: () }
+    // new creates the stream and fails if its queue is already initialized
+    pub fn new() -> Result<Self, StreamInitError> {
+        SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(100))?;
+        Ok(ScancodeStream { _private: () })
+    }
+
+    // new_or_get creates the stream or returns a handle to the existing
+    // queue. All handles share one waker, so only one may be polled.
+    pub fn new_or_get() -> Result<Self, StreamInitError> {
+        match Self::new() {
+            Err(StreamInitError::AlreadyInitialized) => Ok(ScancodeStream { _private: () }),
+            result => result,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_initialized() -> bool {
+        SCANCODE_QUEUE.is_initialized()
     }
 }
 
@@ -67,7 +81,13 @@ impl Stream for ScancodeStream {
 }
 
 pub async fn serial_print_keypresses() {
-    let mut scancodes = ScancodeStream::new();
+    let mut scancodes = match ScancodeStream::new() {
+        Ok(stream) => stream,
+        Err(err) => {
+            serial_error!("serial_print_keypresses: {:?}", err);
+            return;
+        }
+    };
     let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
 
     while let Some(scancode) = scancodes.next().await {
@@ -81,4 +101,3 @@ pub async fn serial_print_keypresses() {
         }
     }
 }
-
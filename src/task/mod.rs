@@ -1,4 +1,5 @@
 use alloc::boxed::Box;
+use conquer_once::TryInitError;
 use core::{
     future::Future,
     pin::Pin,
@@ -10,6 +11,24 @@ pub mod executor;
 pub mod keyboard;
 pub mod tick;
 
+// StreamInitError is returned when creating an interrupt backed stream fails
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamInitError {
+    // the queue of the stream has already been initialized by another stream
+    AlreadyInitialized,
+    // another stream is initializing the queue right now
+    InitInProgress,
+}
+
+impl From<TryInitError> for StreamInitError {
+    fn from(err: TryInitError) -> Self {
+        match err {
+            TryInitError::AlreadyInit => StreamInitError::AlreadyInitialized,
+            TryInitError::WouldBlock => StreamInitError::InitInProgress,
+        }
+    }
+}
+
 pub struct Task {
     id: TaskId,
     future: Pin<Box<dyn Future<Output = ()>>>,
@@ -29,7 +48,7 @@ impl Task {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct TaskId(u64);
+pub struct TaskId(u64);
 
 impl TaskId {
     fn new() -> Self {
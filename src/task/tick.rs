@@ -1,7 +1,9 @@
-use crate::{serial_print, serial_println};
+use super::StreamInitError;
+use crate::{serial_error, serial_print, serial_println};
 use conquer_once::spin::OnceCell;
 use core::{
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
     task::{Context, Poll},
 };
 use crossbeam_queue::ArrayQueue;
@@ -10,13 +12,24 @@ use futures_util::{
     task::AtomicWaker,
 };
 
+// TICKS_PER_SECOND is the rate of the timer interrupt with the default PIT
+// divisor
+pub const TICKS_PER_SECOND: u32 = 18;
+
 static TICK_QUEUE: OnceCell<ArrayQueue<()>> = OnceCell::uninit();
 static WAKER: AtomicWaker = AtomicWaker::new();
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+// ticks returns the number of timer interrupts since boot
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
 
 /// Called by the keyboard interrupt handler
 ///
 /// Must not block or allocate.
 pub(crate) fn add_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
     if let Ok(queue) = TICK_QUEUE.try_get() {
         if let Err(_) = queue.push(()) {
             serial_println!("WARNING: tick queue full; dropping keyboard input");
@@ -33,14 +46,39 @@ pub struct TickStream {
 }
 
 impl TickStream {
-    pub fn new() -> Self {
-        TICK_QUEUE
-            .try_init_once(|| ArrayQueue::new(100))
-            .expect("TickStream::new should only be called once");
-        TickStream { _private: () }
+    // new creates the stream and fails if its queue is already initialized
+    pub fn new() -> Result<Self, StreamInitError> {
+        TICK_QUEUE.try_init_once(|| ArrayQueue::new(100))?;
+        Ok(TickStream { _private: () })
+    }
+
+    // new_or_get creates the stream or returns a handle to the existing
+    // queue. All handles share one waker, so only one may be polled.
+    pub fn new_or_get() -> Result<Self, StreamInitError> {
+        match Self::new() {
+            Err(StreamInitError::AlreadyInitialized) => Ok(TickStream { _private: () }),
+            result => result,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_initialized() -> bool {
+        TICK_QUEUE.is_initialized()
+    }
+
+    // drain drops all pending ticks, e.g. the ones queued up while a game
+    // was paused, without waking the executor
+    pub fn drain(&mut self) {
+        if let Ok(queue) = TICK_QUEUE.try_get() {
+            drain_queue(queue);
+        }
     }
 }
 
+fn drain_queue(queue: &ArrayQueue<()>) {
+    while queue.pop().is_ok() {}
+}
+
 impl Stream for TickStream {
     type Item = ();
 
@@ -66,10 +104,41 @@ impl Stream for TickStream {
 }
 
 pub async fn print_ticks() {
-    let mut ticks = TickStream::new();
+    let mut ticks = match TickStream::new() {
+        Ok(stream) => stream,
+        Err(err) => {
+            serial_error!("print_ticks: {:?}", err);
+            return;
+        }
+    };
 
     while let Some(_) = ticks.next().await {
         serial_print!(".");
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_init() {
+        assert!(TickStream::new().is_ok());
+        assert!(TickStream::is_initialized());
+        assert_eq!(
+            TickStream::new().err(),
+            Some(StreamInitError::AlreadyInitialized)
+        );
+        assert!(TickStream::new_or_get().is_ok());
+    }
+
+    #[test]
+    fn test_drain() {
+        let queue = ArrayQueue::new(100);
+        for _ in 0..50 {
+            queue.push(()).unwrap();
+        }
+        drain_queue(&queue);
+        assert!(queue.is_empty());
+    }
+}
@@ -1,17 +1,109 @@
 extern crate alloc;
-use crate::display::Display;
+use crate::display::{Color, Display, BLOCK_SIZE};
+use crate::serial_println;
+use crate::task::tick::{ticks, TICKS_PER_SECOND};
 use core::fmt::Write;
 
 use alloc::boxed::Box;
+use alloc::string::String;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
 use pc_keyboard::{DecodedKey, KeyCode};
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::SmallRng;
+use rand::Rng;
 
+// MAX_GAMES is the maximum number of games that can be registered
+pub const MAX_GAMES: usize = 8;
+
+// BEST_SCORES holds the best score per registered game. It lives outside of
+// World so the scores survive a World reconstruction, but not a reboot.
+static BEST_SCORES: [AtomicU64; MAX_GAMES] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+pub fn best_score(game_index: usize) -> u64 {
+    BEST_SCORES[game_index].load(Ordering::Relaxed)
+}
+
+// record_score stores score as best score of the game if it beats the
+// previous one and returns whether it did
+pub fn record_score(game_index: usize, score: u64) -> bool {
+    BEST_SCORES[game_index].fetch_max(score, Ordering::Relaxed) < score
+}
+
+// insert_best_score moves the best scores of the games from index on one slot
+// up, so they stay with their games when a game is inserted at index
+fn insert_best_score(index: usize, game_count: usize) {
+    for i in (index..game_count).rev() {
+        BEST_SCORES[i + 1].store(best_score(i), Ordering::Relaxed);
+    }
+    BEST_SCORES[index].store(0, Ordering::Relaxed);
+}
+
+// swap_best_scores swaps the best scores of two games, so they stay with their
+// games when the games are swapped
+fn swap_best_scores(a: usize, b: usize) {
+    let best_a = BEST_SCORES[a].swap(best_score(b), Ordering::Relaxed);
+    BEST_SCORES[b].store(best_a, Ordering::Relaxed);
+}
+
+#[allow(dead_code)]
+pub fn reset_best_scores() {
+    for best in BEST_SCORES.iter() {
+        best.store(0, Ordering::Relaxed);
+    }
+}
+
+// InvalidIndexError is returned when a game is inserted past the end of the
+// game list
 #[derive(PartialEq, Debug)]
+pub struct InvalidIndexError;
+
+#[derive(PartialEq, Eq, Debug)]
 pub enum GameState {
     Live,
+    // the game has ended and shows its end for the remaining ticks
+    Transitioning(u8),
     GameOver,
 }
 
+impl core::fmt::Display for GameState {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            GameState::Live => write!(f, "Live"),
+            GameState::Transitioning(ticks) => write!(f, "Transitioning({})", ticks),
+            GameState::GameOver => write!(f, "GameOver"),
+        }
+    }
+}
+
+// TRANSITION_TICKS is the number of ticks between the end of a game and the
+// game over screen
+pub const TRANSITION_TICKS: u8 = 60;
+
+// transition advances the end of game countdown stored in ticks. The first
+// call starts it, the TRANSITION_TICKS-th call after that returns GameOver.
+pub fn transition(ticks: &mut Option<u8>) -> GameState {
+    let remaining = match *ticks {
+        None => TRANSITION_TICKS,
+        Some(t) => t.saturating_sub(1),
+    };
+    *ticks = Some(remaining);
+    if remaining == 0 {
+        GameState::GameOver
+    } else {
+        GameState::Transitioning(remaining)
+    }
+}
+
 pub trait Game {
     fn on_keypress(&mut self, key: DecodedKey);
     fn reset(&mut self, width: usize, height: usize);
@@ -21,13 +113,131 @@ pub trait Game {
     //    false => continue
     fn step(&mut self) -> GameState;
     fn draw(&mut self, display: &mut Display);
+    fn score(&self) -> u64;
+
+    // serial_dump prints the state of the game to the serial port
+    fn serial_dump(&self) {}
+
+    // can_replay returns whether the finished game can be played back
+    fn can_replay(&self) -> bool {
+        false
+    }
+
+    // start_replay makes the following calls to step and draw play the
+    // finished game back, if can_replay
+    fn start_replay(&mut self) {}
 }
 
 #[derive(Clone, Debug)]
 enum State {
-    Welcome((bool, usize)), // (dirty, selected_game)
+    Welcome {
+        dirty: bool,
+        selected: usize,
+        scroll_tick: u64,
+        scroll_offset: usize,
+    },
+    Countdown(usize, usize), // (game, remaining ticks)
     Running(usize),
-    GameOver(bool), // dirty
+    Paused(usize, bool), // (game, dirty)
+    GameOver(bool),      // dirty
+    HighScores(bool),    // dirty
+}
+
+impl core::fmt::Display for State {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            State::Welcome { selected, .. } => write!(f, "Welcome({})", selected),
+            State::Countdown(game, ticks) => write!(f, "Countdown({}, {})", game, ticks),
+            State::Running(game) => write!(f, "Running({})", game),
+            State::Paused(game, _) => write!(f, "Paused({})", game),
+            State::GameOver(_) => write!(f, "GameOver"),
+            State::HighScores(_) => write!(f, "HighScores"),
+        }
+    }
+}
+
+// COUNTDOWN_TICKS is the length of the countdown before a game starts,
+// 3 seconds at 18 ticks per second
+const COUNTDOWN_TICKS: usize = 54;
+
+// score_line returns the line of the game over screen with the score of the
+// finished game and the best score of that game so far
+fn score_line(game_name: &str, score: usize, best: u64) -> CharsBuf<64> {
+    let mut line = CharsBuf::<64>::new();
+    write!(
+        line,
+        "Game: {}  Score: {}  Best: {}",
+        game_name, score, best
+    )
+    .unwrap();
+    line
+}
+
+// BORDER_COLOR is the border color of the welcome screen
+pub const BORDER_COLOR: Color = Color::DarkBlue;
+
+// INSTRUCTIONS are shown at the bottom of the welcome screen, starting
+// INSTRUCTIONS_OFFSET pixels above the bottom of the screen
+const INSTRUCTIONS_OFFSET: usize = 80;
+const INSTRUCTIONS: &[&str] = &[
+    "Up/Down to select game,",
+    "then press enter to start",
+    "h shows the high scores",
+];
+
+// GAME_LIST_SPACING is the largest distance between two game names on the
+// welcome screen
+const GAME_LIST_SPACING: usize = 30;
+
+// game_list_layout returns the y of the first game name on the welcome
+// screen and the distance between two names, so that count names fit above
+// the instructions
+fn game_list_layout(height: usize, count: usize) -> (usize, usize) {
+    let top = height / 2 + 10;
+    // keep the last name 10 pixels clear of the instructions
+    let room = (height - INSTRUCTIONS_OFFSET - 10).saturating_sub(top);
+    let spacing = room / count.max(1);
+    (top, spacing.min(GAME_LIST_SPACING))
+}
+
+// countdown_label returns what to show with the given ticks remaining
+fn countdown_label(ticks: usize) -> &'static str {
+    if ticks > 36 {
+        "3"
+    } else if ticks > 18 {
+        "2"
+    } else if ticks > 6 {
+        "1"
+    } else {
+        "GO!"
+    }
+}
+
+impl State {
+    // welcome returns a welcome screen state that still needs to be drawn
+    fn welcome(selected: usize) -> Self {
+        State::Welcome {
+            dirty: true,
+            selected,
+            scroll_tick: 0,
+            scroll_offset: 0,
+        }
+    }
+}
+
+// the selected game name on the welcome screen scrolls every SCROLL_INTERVAL
+// ticks by one pixel per tick, for SCROLL_TICKS ticks
+const SCROLL_INTERVAL: u64 = 60;
+const SCROLL_TICKS: u64 = 8;
+
+// welcome_scroll_offset returns the pixel offset of the selected game name
+fn welcome_scroll_offset(scroll_tick: u64) -> usize {
+    let t = scroll_tick % SCROLL_INTERVAL;
+    if t < SCROLL_TICKS {
+        t as usize
+    } else {
+        0
+    }
 }
 
 unsafe impl Send for World {}
@@ -38,35 +248,260 @@ pub struct World {
     state: State,
     width: usize,
     height: usize,
+    last_score: usize,
+    last_game_name: &'static str,
+    last_best: u64,
+    // the index of the finished game and whether it can be replayed
+    last_game: usize,
+    last_replayable: bool,
+    // the frame below the pause overlay
+    pause_snapshot: Option<Vec<u8>>,
+    new_best: bool,
+    // the last INPUT_LOG_SIZE keys, log_head is the next slot to write
+    input_log: [Option<DecodedKey>; INPUT_LOG_SIZE],
+    log_head: usize,
+    // the tick rate counter toggled with F12, measured over windows of
+    // TICKS_PER_SECOND timer interrupts
+    show_fps: bool,
+    tick_rate: u64,
+    rate_window_start: u64,
+    rate_window_ticks: u64,
 }
 
+// GAME_OVER_SHADE is drawn over the last frame of a game on game over
+const GAME_OVER_SHADE: Color = Color::RGBA32(0xc0000000);
+
+// INPUT_LOG_SIZE is the number of keypresses kept for dump_input_log
+const INPUT_LOG_SIZE: usize = 64;
+
 impl World {
     pub fn new(width: usize, height: usize) -> Self {
         Self {
             games: Vec::new(),
             game_names: Vec::new(),
-            state: State::Welcome((true, 0)),
+            state: State::welcome(0),
             width,
             height,
+            last_score: 0,
+            last_game_name: "",
+            last_game: 0,
+            last_replayable: false,
+            last_best: 0,
+            pause_snapshot: None,
+            new_best: false,
+            input_log: [None; INPUT_LOG_SIZE],
+            log_head: 0,
+            show_fps: false,
+            tick_rate: 0,
+            rate_window_start: 0,
+            rate_window_ticks: 0,
         }
     }
 
-    pub fn add_game(&mut self, mut game: Box<dyn Game>, name: &'static str) {
+    pub fn add_game(&mut self, game: Box<dyn Game>, name: &'static str) {
+        let index = self.games.len();
+        self.add_game_at_index(game, name, index).unwrap();
+    }
+
+    // add_game_at_index registers a game at the given position of the game
+    // list, keeping the currently selected game highlighted
+    pub fn add_game_at_index(
+        &mut self,
+        mut game: Box<dyn Game>,
+        name: &'static str,
+        index: usize,
+    ) -> Result<(), InvalidIndexError> {
+        if index > self.games.len() {
+            return Err(InvalidIndexError);
+        }
+        assert!(self.games.len() < MAX_GAMES, "too many games registered");
         game.reset(self.width, self.height);
-        self.games.push(game);
-        self.game_names.push(name);
+        insert_best_score(index, self.games.len());
+        self.games.insert(index, game);
+        self.game_names.insert(index, name);
+        if let State::Welcome {
+            ref mut selected,
+            ref mut dirty,
+            ..
+        } = self.state
+        {
+            if index <= *selected && self.games.len() > 1 {
+                *selected += 1;
+            }
+            *dirty = true;
+        }
+        Ok(())
+    }
+
+    // swap_games swaps the position of two games in the game list and
+    // returns false if one of the indices is out of range
+    #[allow(dead_code)]
+    pub fn swap_games(&mut self, a: usize, b: usize) -> bool {
+        if a >= self.games.len() || b >= self.games.len() {
+            return false;
+        }
+        self.games.swap(a, b);
+        self.game_names.swap(a, b);
+        swap_best_scores(a, b);
+        if let State::Welcome {
+            ref mut selected,
+            ref mut dirty,
+            ..
+        } = self.state
+        {
+            if *selected == a {
+                *selected = b;
+            } else if *selected == b {
+                *selected = a;
+            }
+            *dirty = true;
+        }
+        true
+    }
+
+    pub fn game_names(&self) -> &[&'static str] {
+        &self.game_names
+    }
+
+    // name_width returns the length of the longest registered game name, the
+    // game lists pad the names to it
+    fn name_width(&self) -> usize {
+        self.game_names
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn game_count(&self) -> usize {
+        self.games.len()
+    }
+
+    // selected_game_index returns the game selected on the welcome screen
+    pub fn selected_game_index(&self) -> Option<usize> {
+        match self.state {
+            State::Welcome { selected, .. } => Some(selected),
+            _ => None,
+        }
+    }
+
+    // serialize_welcome_config returns the welcome screen settings as
+    // key=value pairs separated by ';', the format apply_config_string reads
+    pub fn serialize_welcome_config(&self) -> CharsBuf<256> {
+        let mut config = CharsBuf::<256>::new();
+        let selected = self.selected_game_index().unwrap_or(0);
+        if let Some(name) = self.game_names.get(selected) {
+            write!(config, "game={}", name).ok();
+        }
+        config
+    }
+
+    // apply_config_string applies the settings of a config string like
+    // "game=snake". Unknown keys and values are ignored.
+    pub fn apply_config_string(&mut self, config: &str) {
+        for pair in config.split(';') {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().unwrap_or("").trim();
+            match key {
+                "game" => {
+                    if let Some(i) = self.game_names.iter().position(|name| *name == value) {
+                        if let State::Welcome { .. } = self.state {
+                            self.state = State::welcome(i);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // is_paused returns whether a game is paused
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, State::Paused(_, _))
+    }
+
+    // logged_keys returns the logged keypresses, oldest first
+    fn logged_keys(&self) -> impl Iterator<Item = DecodedKey> + '_ {
+        let (newer, older) = self.input_log.split_at(self.log_head);
+        older.iter().chain(newer.iter()).filter_map(|key| *key)
+    }
+
+    // dump_input_log prints the last keypresses to the serial port
+    pub fn dump_input_log(&self) {
+        serial_println!("last keypresses:");
+        for key in self.logged_keys() {
+            serial_println!("  {:?}", key);
+        }
+    }
+
+    // count_tick counts a handled tick and updates the tick rate once the
+    // timer interrupt counter now is a second past the current window
+    fn count_tick(&mut self, now: u64) {
+        self.rate_window_ticks += 1;
+        let elapsed = now.saturating_sub(self.rate_window_start);
+        if elapsed >= TICKS_PER_SECOND as u64 {
+            self.tick_rate = self.rate_window_ticks * TICKS_PER_SECOND as u64 / elapsed;
+            self.rate_window_start = now;
+            self.rate_window_ticks = 0;
+        }
+    }
+
+    // tick_rate_region returns the area of the tick rate counter in the top
+    // right corner
+    fn tick_rate_region(&self) -> (usize, usize, usize, usize) {
+        (
+            self.width - 3 * BLOCK_SIZE - 8 * 8,
+            3 * BLOCK_SIZE,
+            8 * 8,
+            8,
+        )
+    }
+
+    fn draw_tick_rate(&self, display: &mut Display) {
+        let mut text = CharsBuf::<8>::new();
+        write!(text, "{:>3} tps", self.tick_rate).unwrap();
+        let (x, y, w, h) = self.tick_rate_region();
+        display.clear_rect(x, y, w, h);
+        display.draw_text_at(x, y, text.chars(), Color::White, Color::Black);
     }
 
     pub fn on_keypress(&mut self, key: DecodedKey, display: &mut Display) {
+        self.input_log[self.log_head] = Some(key);
+        self.log_head = (self.log_head + 1) % INPUT_LOG_SIZE;
+
+        if key == DecodedKey::RawKey(KeyCode::F12) {
+            self.show_fps = !self.show_fps;
+            if !self.show_fps {
+                let (x, y, w, h) = self.tick_rate_region();
+                display.clear_rect(x, y, w, h);
+            }
+            return;
+        }
+
         match self.state {
             State::Running(i) if key == DecodedKey::Unicode('r') => {
-                self.state = State::Welcome((true, 0));
+                self.state = State::welcome(0);
+            }
+            State::Running(i) if key == DecodedKey::Unicode('p') => {
+                self.state = State::Paused(i, true);
             }
             State::Running(i) => {
                 let w = &mut self.games[i];
                 w.on_keypress(key);
             }
-            State::Welcome((_, selected_game)) => {
+            State::Paused(i, _) if key == DecodedKey::Unicode('p') => {
+                if let Some(snapshot) = self.pause_snapshot.take() {
+                    let (x, y, w, h) = self.pause_region();
+                    display.restore_region(x, y, w, h, &snapshot);
+                }
+                self.state = State::Running(i);
+            }
+            State::Paused(_, _) => {}
+            State::Welcome {
+                selected: selected_game,
+                ..
+            } => {
                 match key {
                     DecodedKey::Unicode('q') => {
                         // TODO: shutdown properly
@@ -80,29 +515,45 @@ impl World {
                     }
                     DecodedKey::Unicode('\n') | DecodedKey::RawKey(KeyCode::Enter) => {
                         display.clear();
-                        self.state = State::Running(selected_game);
-                        let w = &mut self.games[selected_game];
-                        w.reset(self.width, self.height);
+                        self.state = State::Countdown(selected_game, COUNTDOWN_TICKS);
+                        self.draw_countdown(display, COUNTDOWN_TICKS);
                     }
                     DecodedKey::RawKey(KeyCode::ArrowDown) => {
-                        self.state = State::Welcome((true, (selected_game + 1) % self.games.len()));
+                        self.state = State::welcome((selected_game + 1) % self.game_count());
                     }
                     DecodedKey::RawKey(KeyCode::ArrowUp) => {
                         let game = if selected_game == 0 {
-                            self.games.len() - 1
+                            self.game_count() - 1
                         } else {
                             selected_game - 1
                         };
 
-                        self.state = State::Welcome((true, game));
+                        self.state = State::welcome(game);
+                    }
+                    DecodedKey::Unicode('h') => {
+                        self.state = State::HighScores(true);
                     }
                     _ => {}
                 };
             }
+            State::Countdown(_, _) => {}
+            State::HighScores(_) => match key {
+                DecodedKey::Unicode('q')
+                | DecodedKey::Unicode('\u{1b}')
+                | DecodedKey::RawKey(KeyCode::Escape) => {
+                    self.state = State::welcome(0);
+                }
+                _ => {}
+            },
             State::GameOver(_) => {
                 match key {
                     DecodedKey::Unicode('r') => {
-                        self.state = State::Welcome((true, 0));
+                        self.state = State::welcome(0);
+                    }
+                    DecodedKey::Unicode('p') if self.last_replayable => {
+                        display.clear();
+                        self.games[self.last_game].start_replay();
+                        self.state = State::Running(self.last_game);
                     }
                     _ => {}
                 };
@@ -111,19 +562,68 @@ impl World {
     }
 
     pub fn on_tick(&mut self, display: &mut Display) {
+        self.count_tick(ticks());
+        self.advance_state(display);
+        // drawn last so it stays on top of the screen
+        if self.show_fps {
+            self.draw_tick_rate(display);
+        }
+    }
+
+    // advance_state moves the current screen or game on by one tick
+    fn advance_state(&mut self, display: &mut Display) {
         match self.state {
+            State::Countdown(i, ticks) if ticks <= 1 => {
+                display.clear();
+                self.state = State::Running(i);
+                self.new_best = false;
+                let game = &mut self.games[i];
+                game.reset(self.width, self.height);
+            }
+            State::Countdown(i, ticks) => {
+                self.state = State::Countdown(i, ticks - 1);
+                if countdown_label(ticks - 1) != countdown_label(ticks) {
+                    self.draw_countdown(display, ticks - 1);
+                }
+            }
             State::Running(i) => {
                 let game = &mut self.games[i];
-                if game.step() == GameState::GameOver {
+                let state = game.step();
+                if record_score(i, game.score()) {
+                    self.new_best = true;
+                }
+                if state == GameState::GameOver {
+                    self.last_score = game.score() as usize;
+                    self.last_game_name = self.game_names[i];
+                    self.last_game = i;
+                    self.last_replayable = game.can_replay();
+                    self.last_best = best_score(i);
+                    game.serial_dump();
+                    self.dump_input_log();
                     self.state = State::GameOver(true);
                     return;
                 }
-                game.draw(display);
+                if display.frame_due() {
+                    game.draw(display);
+                }
             }
-            State::Welcome((dirty, selected_game)) => {
+            State::Welcome {
+                dirty,
+                selected,
+                scroll_tick,
+                scroll_offset,
+            } => {
+                let scroll_tick = scroll_tick + 1;
+                self.state = State::Welcome {
+                    dirty: false,
+                    selected,
+                    scroll_tick,
+                    scroll_offset: welcome_scroll_offset(scroll_tick),
+                };
                 if dirty {
                     self.draw_welcome(display);
-                    self.state = State::Welcome((false, selected_game));
+                } else if welcome_scroll_offset(scroll_tick) != scroll_offset {
+                    self.draw_game_list(display);
                 }
             }
             State::GameOver(dirty) => {
@@ -132,23 +632,120 @@ impl World {
                     self.state = State::GameOver(false);
                 }
             }
+            State::Paused(i, dirty) => {
+                if dirty {
+                    self.draw_paused(display);
+                    self.state = State::Paused(i, false);
+                }
+            }
+            State::HighScores(dirty) => {
+                if dirty {
+                    self.draw_high_scores(display);
+                    self.state = State::HighScores(false);
+                }
+            }
         }
     }
 
-    fn draw_game_over(&mut self, display: &mut Display) {
+    // pause_region returns the area covered by the pause overlay
+    fn pause_region(&self) -> (usize, usize, usize, usize) {
+        (self.width / 4, self.height / 2 - 20, self.width / 2, 40)
+    }
+
+    // draw_paused saves the game frame below the overlay, so it can be
+    // restored on unpause, and darkens it to half brightness behind the text
+    fn draw_paused(&mut self, display: &mut Display) {
+        let (x, y, w, h) = self.pause_region();
+        let snapshot = display.snapshot_region(x, y, w, h);
+        let darkened: Vec<u8> = snapshot.iter().map(|b| b / 2).collect();
+        display.restore_region(x, y, w, h, &darkened);
+        self.pause_snapshot = Some(snapshot);
+        display.write_str_centered("PAUSED - press p to continue", y + 16);
+    }
+
+    // high_score_lines returns the lines of the high scores table
+    fn high_score_lines(&self) -> Vec<String> {
+        if (0..self.game_count()).all(|i| best_score(i) == 0) {
+            return alloc::vec![String::from("No games played yet")];
+        }
+        let width = self.name_width();
+        self.game_names()
+            .iter()
+            .enumerate()
+            .map(|(i, name)| alloc::format!("{:<width$} {:>8}", name, best_score(i), width = width))
+            .collect()
+    }
+
+    fn draw_high_scores(&mut self, display: &mut Display) {
+        let (w, h) = (
+            display.info.unwrap().horizontal_resolution,
+            display.info.unwrap().vertical_resolution,
+        );
         display.clear();
-        let msg = "GAME OVER";
-        display.set_xy(
-            display.info.unwrap().horizontal_resolution / 2 - msg.len() * 8 / 2,
-            display.info.unwrap().vertical_resolution / 2,
+        display.draw_borders_with_color(BORDER_COLOR);
+        display.write_str_centered("HIGH SCORES", h / 2 - 40);
+
+        let lines = self.high_score_lines();
+        let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+        let longest = lines
+            .iter()
+            .map(|l| display.measure_text(l).0)
+            .max()
+            .unwrap_or(0);
+        display.draw_char_matrix(
+            w.saturating_sub(longest) / 2,
+            h / 2 - 10,
+            &lines,
+            display.color,
+            display.background_color,
         );
-        display.write_str(msg).unwrap();
-        let msg = "(press 'r' to restart)";
-        display.set_xy(
-            display.info.unwrap().horizontal_resolution / 2 - msg.len() * 8 / 2,
-            display.info.unwrap().vertical_resolution / 2 + 10,
+        display.write_str_centered("press q or escape to return", self.height - 80);
+    }
+
+    fn draw_countdown(&mut self, display: &mut Display, ticks: usize) {
+        let y = display.info.unwrap().vertical_resolution / 2 - 16;
+        display.draw_rect(
+            0,
+            y,
+            display.info.unwrap().horizontal_resolution,
+            32,
+            Color::Black,
         );
-        display.write_str(msg);
+        display.set_font_scale(4);
+        display.write_str_centered(countdown_label(ticks), y);
+        display.set_font_scale(1);
+    }
+
+    fn draw_game_over(&mut self, display: &mut Display) {
+        let (w, h) = (
+            display.info.unwrap().horizontal_resolution,
+            display.info.unwrap().vertical_resolution,
+        );
+        // dim the last frame of the game instead of clearing it
+        display.draw_rect(0, 0, w, h, GAME_OVER_SHADE);
+        let y = h / 2;
+        // twice the size, ending just above the score line
+        display.set_font_scale(2);
+        display.write_str_centered("GAME OVER", y - 8);
+        display.set_font_scale(1);
+        // with a bar fading out to the right above it
+        display.draw_gradient_rect((w / 4, y - 14, w / 2, 3), Color::Red, Color::Black, true);
+
+        let score = score_line(self.last_game_name, self.last_score, self.last_best);
+        display.write_chars_centered(score.chars(), y + 10, display.color);
+        if self.new_best {
+            let color = display.color;
+            display.color = Color::RGB32(0xffd700);
+            display.set_underline(true);
+            display.write_str_centered("New Best!", y + 20);
+            display.set_underline(false);
+            display.color = color;
+        }
+        if self.last_replayable {
+            display.write_str_centered("(press 'r' to restart, 'p' to replay)", y + 30);
+        } else {
+            display.write_str_centered("(press 'r' to restart)", y + 30);
+        }
         // serial_println!("GAME OVER");
     }
 
@@ -158,38 +755,45 @@ impl World {
             display.info.unwrap().vertical_resolution,
         );
         display.clear();
-        display.draw_borders();
+        display.draw_borders_with_color(BORDER_COLOR);
+
+        let y_pos = h / 2 - 30;
 
-        let mut y_pos = h / 2 - 30;
+        let logo_height = crate::display::BOOT_LOGO.len() * crate::display::LOGO_SCALE;
+        display.set_xy(0, y_pos - logo_height - 20);
+        display.print_boot_logo();
 
         let msg = "<=== Welcome to SnakeOS ===>";
-        display.set_xy(w / 2 - ((msg.len() / 2) * 8), y_pos);
+        let (msg_width, msg_height) = display.measure_text(msg);
+        let x = w.saturating_sub(msg_width) / 2;
+        display.set_xy(x, y_pos);
         write!(display, "{}", msg);
-        y_pos += 40;
-
-        let selected_game = match self.state {
-            State::Welcome((_, v)) => v,
-            _ => unreachable!(),
-        };
-
-        for (i, name) in self.game_names.iter().enumerate() {
-            display.set_xy(w / 2 - 40, y_pos);
-            if selected_game == i {
-                write!(display, "*  {}", name);
-            } else {
-                write!(display, "   {}", name);
-            }
-            y_pos += 30;
+        // frame the title with bars fading in from both sides
+        for bar_y in [y_pos - 5, y_pos + msg_height + 1].iter() {
+            let half = msg_width / 2;
+            display.draw_gradient_rect((x, *bar_y, half, 2), Color::Black, BORDER_COLOR, true);
+            let (x, w) = (x + half, msg_width - half);
+            display.draw_gradient_rect((x, *bar_y, w, 2), BORDER_COLOR, Color::Black, true);
         }
 
-        let msg = "Up/Down to select game, then press enter to start";
-        display.set_xy(w / 2 - ((msg.len() / 2) * 8), self.height - 80);
-        write!(display, "{}", msg);
-        y_pos += 20;
+        self.draw_game_list(display);
+
+        let longest = INSTRUCTIONS
+            .iter()
+            .map(|l| display.measure_text(l).0)
+            .max()
+            .unwrap_or(0);
+        display.draw_char_matrix(
+            w.saturating_sub(longest) / 2,
+            self.height - INSTRUCTIONS_OFFSET,
+            INSTRUCTIONS,
+            display.color,
+            display.background_color,
+        );
 
         let footer = "by trusch & MoZhonghua";
         display.set_xy(
-            w - footer.len() * 8 - 3 * crate::display::BLOCK_SIZE,
+            w - display.measure_text(footer).0 - 3 * crate::display::BLOCK_SIZE,
             h - 4 * crate::display::BLOCK_SIZE,
         );
         write!(display, "{}", footer);
@@ -198,12 +802,42 @@ impl World {
             display.write_block(0, 0, crate::Color::Black);
         }
     }
+
+    // draw_game_list draws the games of the welcome screen, with the selected
+    // one shifted by the current scroll offset
+    fn draw_game_list(&mut self, display: &mut Display) {
+        let (selected_game, scroll_offset) = match self.state {
+            State::Welcome {
+                selected,
+                scroll_offset,
+                ..
+            } => (selected, scroll_offset),
+            _ => unreachable!(),
+        };
+
+        let w = display.info.unwrap().horizontal_resolution;
+        let h = display.info.unwrap().vertical_resolution;
+        let (mut y_pos, spacing) = game_list_layout(h, self.game_names.len());
+        let width = self.name_width();
+        for (i, name) in self.game_names().iter().enumerate() {
+            display.draw_rect(w / 2 - 40, y_pos, w / 2, 8, Color::Black);
+            let best = best_score(i);
+            if selected_game == i {
+                display.set_xy(w / 2 - 40 + scroll_offset, y_pos);
+                write!(display, "*  {:<width$} best: {}", name, best, width = width).unwrap();
+            } else {
+                display.set_xy(w / 2 - 40, y_pos);
+                write!(display, "   {:<width$} best: {}", name, best, width = width).unwrap();
+            }
+            y_pos += spacing;
+        }
+    }
 }
 
 // Position on screen in pixels
 // (0,0) -> left top corner
 // (width,height) -> right bottom corner
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct ScreenPos {
     pub x: usize,
     pub y: usize,
@@ -274,6 +908,35 @@ pub enum Direction {
     Left,
 }
 
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Right => Direction::Left,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+        }
+    }
+}
+
+// a uniformly distributed direction, so callers can write rng.gen()
+impl Distribution<Direction> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Direction {
+        match rng.next_u64() % 4 {
+            0 => Direction::Up,
+            1 => Direction::Down,
+            2 => Direction::Left,
+            _ => Direction::Right,
+        }
+    }
+}
+
+// random_direction returns a uniformly distributed direction
+#[allow(dead_code)]
+pub fn random_direction(rng: &mut SmallRng) -> Direction {
+    rng.gen()
+}
+
 pub struct CharsBuf<const T: usize> {
     data: [char; T],
     written_bytes: usize,
@@ -315,4 +978,343 @@ mod tests {
         let mut n2 = CharsBuf::<2>::new();
         write!(n2, "{}", 1);
     }
+
+    struct MockGame {
+        over: bool,
+        score: u64,
+    }
+
+    impl Game for MockGame {
+        fn on_keypress(&mut self, _key: DecodedKey) {}
+        fn reset(&mut self, _width: usize, _height: usize) {
+            self.over = false;
+        }
+        fn step(&mut self) -> GameState {
+            if self.over {
+                GameState::GameOver
+            } else {
+                GameState::Live
+            }
+        }
+        fn draw(&mut self, _display: &mut Display) {}
+        fn score(&self) -> u64 {
+            self.score
+        }
+    }
+
+    // mock_game returns a running MockGame without score
+    fn mock_game() -> Box<MockGame> {
+        Box::new(MockGame {
+            over: false,
+            score: 0,
+        })
+    }
+
+    #[test]
+    fn game_over_captures_score() {
+        let mut display = Display::new();
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "mock");
+        world.state = State::Running(0);
+
+        world.on_tick(&mut display);
+        assert!(matches!(world.state, State::Running(0)));
+
+        let game = MockGame {
+            over: true,
+            score: 42,
+        };
+        world.games[0] = Box::new(game);
+        world.on_tick(&mut display);
+        assert!(matches!(world.state, State::GameOver(true)));
+        assert_eq!(world.last_score, 42);
+        assert_eq!(world.last_game_name, "mock");
+    }
+
+    #[test]
+    fn game_over_score_line() {
+        let line = score_line("snake", 7, 12);
+        let expected: Vec<char> = "Game: snake  Score: 7  Best: 12".chars().collect();
+        assert_eq!(line.chars(), &expected[..]);
+    }
+
+    #[test]
+    fn countdown_starts_game() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "mock");
+
+        world.on_keypress(DecodedKey::RawKey(KeyCode::Enter), &mut display);
+        assert!(matches!(world.state, State::Countdown(0, COUNTDOWN_TICKS)));
+        for _ in 0..COUNTDOWN_TICKS - 1 {
+            world.on_tick(&mut display);
+            assert!(matches!(world.state, State::Countdown(0, _)));
+        }
+        world.on_tick(&mut display);
+        assert!(matches!(world.state, State::Running(0)));
+    }
+
+    #[test]
+    fn random_direction_covers_all() {
+        use rand::SeedableRng;
+        let mut rng = SmallRng::seed_from_u64(42);
+        let mut seen = [false; 4];
+        for _ in 0..1000 {
+            let i = match random_direction(&mut rng) {
+                Direction::Up => 0,
+                Direction::Right => 1,
+                Direction::Down => 2,
+                Direction::Left => 3,
+            };
+            seen[i] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+    }
+
+    #[test]
+    fn tick_rate_counter() {
+        let mut world = World::new(640, 480);
+        let mut display = Display::new_for_test(640, 480);
+        world.on_keypress(DecodedKey::RawKey(KeyCode::F12), &mut display);
+        assert!(world.show_fps);
+
+        // 9 handled ticks within 18 timer interrupts are 9 ticks per second
+        for now in (0..18).step_by(2) {
+            world.count_tick(now);
+        }
+        world.count_tick(18);
+        assert_eq!(world.tick_rate, 10);
+
+        world.on_tick(&mut display);
+        let (x, y, w, h) = world.tick_rate_region();
+        let is_blank = |display: &Display| {
+            display
+                .snapshot_region(x, y, w, h)
+                .iter()
+                .all(|byte| *byte == 0)
+        };
+        assert!(!is_blank(&display));
+
+        world.on_keypress(DecodedKey::RawKey(KeyCode::F12), &mut display);
+        assert!(!world.show_fps);
+        assert!(is_blank(&display));
+    }
+
+    #[test]
+    fn input_log_wraps() {
+        let mut world = World::new(640, 480);
+        let mut display = Display::new_for_test(640, 480);
+        let keys = ['a', 'b', 'c', 'd'];
+        for i in 0..INPUT_LOG_SIZE + 2 {
+            world.on_keypress(DecodedKey::Unicode(keys[i % 4]), &mut display);
+        }
+        assert_eq!(world.log_head, 2);
+        let logged: Vec<DecodedKey> = world.logged_keys().collect();
+        assert_eq!(logged.len(), INPUT_LOG_SIZE);
+        assert_eq!(logged[0], DecodedKey::Unicode('c'));
+        assert_eq!(logged[INPUT_LOG_SIZE - 1], DecodedKey::Unicode('b'));
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "snake");
+        world.add_game(mock_game(), "2048");
+        let config: String = world.serialize_welcome_config().chars().iter().collect();
+        assert_eq!(config, "game=snake");
+
+        world.apply_config_string("theme=2;game=2048;difficulty=");
+        assert_eq!(world.selected_game_index(), Some(1));
+        let config: String = world.serialize_welcome_config().chars().iter().collect();
+        assert_eq!(config, "game=2048");
+
+        world.apply_config_string("game=tetris");
+        assert_eq!(world.selected_game_index(), Some(1));
+
+        let mut other = World::new(640, 480);
+        other.add_game(mock_game(), "snake");
+        other.add_game(mock_game(), "2048");
+        other.apply_config_string(&config);
+        assert_eq!(other.selected_game_index(), Some(1));
+    }
+
+    #[test]
+    fn game_accessors() {
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "snake");
+        world.add_game(mock_game(), "2048");
+
+        assert_eq!(world.game_names(), ["snake", "2048"]);
+        assert_eq!(world.game_count(), 2);
+        assert_eq!(world.selected_game_index(), Some(0));
+
+        world.state = State::Running(1);
+        assert_eq!(world.selected_game_index(), None);
+    }
+
+    #[test]
+    fn add_game_at_index() {
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "snake");
+        world.add_game(mock_game(), "2048");
+        world.state = State::welcome(1);
+
+        assert_eq!(
+            world.add_game_at_index(mock_game(), "tetris", 3),
+            Err(InvalidIndexError)
+        );
+        assert_eq!(world.add_game_at_index(mock_game(), "tetris", 1), Ok(()));
+        assert_eq!(world.game_names, ["snake", "tetris", "2048"]);
+        assert_eq!(world.selected_game_index().unwrap(), 2);
+
+        assert_eq!(world.add_game_at_index(mock_game(), "pong", 3), Ok(()));
+        assert_eq!(world.game_names, ["snake", "tetris", "2048", "pong"]);
+        assert_eq!(world.selected_game_index().unwrap(), 2);
+
+        assert!(world.swap_games(0, 2));
+        assert_eq!(world.game_names, ["2048", "tetris", "snake", "pong"]);
+        assert_eq!(world.selected_game_index().unwrap(), 0);
+        assert!(!world.swap_games(0, 4));
+    }
+
+    #[test]
+    fn state_display() {
+        assert_ne!(GameState::Live, GameState::GameOver);
+        assert_eq!(alloc::format!("{}", GameState::Live), "Live");
+        assert_eq!(
+            alloc::format!("{}", GameState::Transitioning(3)),
+            "Transitioning(3)"
+        );
+        assert_eq!(alloc::format!("{}", State::welcome(1)), "Welcome(1)");
+        assert_eq!(alloc::format!("{}", State::Running(0)), "Running(0)");
+    }
+
+    #[test]
+    fn high_scores_screen() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "mock");
+
+        world.on_keypress(DecodedKey::Unicode('h'), &mut display);
+        assert!(matches!(world.state, State::HighScores(true)));
+        world.on_tick(&mut display);
+        assert!(matches!(world.state, State::HighScores(false)));
+
+        world.on_keypress(DecodedKey::RawKey(KeyCode::Escape), &mut display);
+        assert_eq!(world.selected_game_index(), Some(0));
+
+        world.on_keypress(DecodedKey::Unicode('h'), &mut display);
+        world.on_keypress(DecodedKey::Unicode('q'), &mut display);
+        assert_eq!(world.selected_game_index(), Some(0));
+    }
+
+    #[test]
+    fn high_score_lines_pad_to_longest_name() {
+        // slots 0 to 3 are used by the other tests
+        let _lock = BEST_SCORES_LOCK.lock();
+        let mut world = World::new(640, 480);
+        for name in ["a", "b", "c", "d", "e", "snake deluxe"].iter() {
+            world.add_game(mock_game(), name);
+        }
+        BEST_SCORES[5].store(7, Ordering::Relaxed);
+        let lines = world.high_score_lines();
+        assert_eq!(lines[5], "snake deluxe        7");
+        assert!(lines[4].starts_with("e            "));
+        assert!(lines.iter().all(|line| line.len() == 12 + 1 + 8));
+    }
+
+    #[test]
+    fn pause_restores_frame() {
+        let mut display = Display::new_for_test(640, 480);
+        let mut world = World::new(640, 480);
+        world.add_game(mock_game(), "mock");
+        world.state = State::Running(0);
+        display.draw_rect(0, 0, 640, 480, Color::Blue);
+        let before = display.snapshot_region(0, 0, 640, 480);
+
+        world.on_keypress(DecodedKey::Unicode('p'), &mut display);
+        world.on_tick(&mut display);
+        assert!(matches!(world.state, State::Paused(0, false)));
+        assert!(world.is_paused());
+        assert_ne!(display.snapshot_region(0, 0, 640, 480), before);
+
+        world.on_keypress(DecodedKey::Unicode('p'), &mut display);
+        assert!(matches!(world.state, State::Running(0)));
+        assert!(!world.is_paused());
+        assert_eq!(display.snapshot_region(0, 0, 640, 480), before);
+    }
+
+    #[test]
+    fn welcome_scroll() {
+        assert_eq!(welcome_scroll_offset(0), 0);
+        assert_eq!(welcome_scroll_offset(1), 1);
+        assert_eq!(welcome_scroll_offset(7), 7);
+        assert_eq!(welcome_scroll_offset(8), 0);
+        assert_eq!(welcome_scroll_offset(59), 0);
+        assert_eq!(welcome_scroll_offset(60), 0);
+        assert_eq!(welcome_scroll_offset(63), 3);
+    }
+
+    #[test]
+    fn game_list_fits_above_instructions() {
+        // few games keep the full spacing
+        assert_eq!(game_list_layout(480, 2), (250, GAME_LIST_SPACING));
+        for count in 1..=8 {
+            let (top, spacing) = game_list_layout(480, count);
+            // the last name, 8 pixels high, ends above the instructions
+            let last = top + (count - 1) * spacing;
+            assert!(last + 8 < 480 - INSTRUCTIONS_OFFSET, "{} games", count);
+        }
+    }
+
+    // BEST_SCORES_LOCK keeps the tests that check BEST_SCORES from resetting
+    // each other's scores
+    static BEST_SCORES_LOCK: spin::Mutex<()> = spin::Mutex::new(());
+
+    #[test]
+    fn best_scores_update() {
+        let _lock = BEST_SCORES_LOCK.lock();
+        reset_best_scores();
+        assert_eq!(best_score(3), 0);
+
+        assert!(record_score(3, 10));
+        assert!(!record_score(3, 7));
+        assert!(!record_score(3, 10));
+        assert_eq!(best_score(3), 10);
+        assert_eq!(best_score(4), 0);
+
+        reset_best_scores();
+        assert_eq!(best_score(3), 0);
+    }
+
+    #[test]
+    fn best_scores_follow_inserted_games() {
+        // slots 0 to 3 are used by the other tests
+        let _lock = BEST_SCORES_LOCK.lock();
+        let mut world = World::new(640, 480);
+        for name in ["a", "b", "c", "d", "e", "f"].iter() {
+            world.add_game(mock_game(), name);
+        }
+        record_score(5, 10);
+        world.add_game_at_index(mock_game(), "g", 5).unwrap();
+        assert_eq!(world.game_names[6], "f");
+        assert_eq!(best_score(6), 10);
+        assert_eq!(best_score(5), 0);
+    }
+
+    #[test]
+    fn best_scores_follow_swapped_games() {
+        // slots 0 to 3 are used by the other tests
+        let _lock = BEST_SCORES_LOCK.lock();
+        let mut world = World::new(640, 480);
+        for name in ["a", "b", "c", "d", "e", "f", "g"].iter() {
+            world.add_game(mock_game(), name);
+        }
+        BEST_SCORES[4].store(7, Ordering::Relaxed);
+        BEST_SCORES[6].store(12, Ordering::Relaxed);
+        assert!(world.swap_games(4, 6));
+        assert_eq!(world.game_names[4], "g");
+        assert_eq!(best_score(4), 12);
+        assert_eq!(best_score(6), 7);
+    }
 }